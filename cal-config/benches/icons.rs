@@ -0,0 +1,72 @@
+//! Icon lookup benchmark against a synthetic icon theme tree
+
+use std::{fs, path::Path};
+
+use cal_config::icons::IconLookup;
+use criterion::{Criterion, criterion_group, criterion_main};
+
+/// Sizes of the synthetic theme directories
+const SIZES: &[u32] = &[16, 22, 24, 32, 48, 64, 96, 128, 256, 512];
+/// Contexts of the synthetic theme directories
+const CONTEXTS: &[&str] = &["apps", "actions", "devices", "mimetypes", "places"];
+/// Number of icons in each directory
+const ICONS_PER_DIR: usize = 500;
+
+/// Create a theme with the given parent, and `ICONS_PER_DIR` icons in each of its directories
+fn create_theme(base_dir: &Path, name: &str, inherits: Option<&str>) {
+    let theme_dir = base_dir.join(name);
+    let mut directories = Vec::new();
+    let mut groups = String::new();
+
+    for size in SIZES {
+        for context in CONTEXTS {
+            let dir = format!("{size}x{size}/{context}");
+            fs::create_dir_all(theme_dir.join(&dir)).unwrap();
+
+            for i in 0..ICONS_PER_DIR {
+                fs::write(theme_dir.join(&dir).join(format!("{context}-{i}.png")), "").unwrap();
+            }
+
+            groups.push_str(&format!("\n[{dir}]\nSize={size}\nType=Fixed\n"));
+            directories.push(dir);
+        }
+    }
+
+    let inherits = inherits.map(|i| format!("Inherits={i}\n")).unwrap_or_default();
+    let index = format!(
+        "[Icon Theme]\nName={name}\n{inherits}Directories={}\n{groups}",
+        directories.join(",")
+    );
+    fs::write(theme_dir.join("index.theme"), index).unwrap();
+}
+
+fn icons_benchmark(c: &mut Criterion) {
+    let base_dir = std::env::temp_dir().join("cal-icons-bench");
+    if !base_dir.exists() {
+        create_theme(&base_dir, "hicolor", None);
+        create_theme(&base_dir, "bench", Some("hicolor"));
+    }
+
+    let base_dirs = vec![base_dir];
+    let theme = Some("bench".to_string());
+
+    // Icons referenced by a typical set of desktop entries, some of them missing
+    let names: Vec<String> = (0..200)
+        .map(|i| match i % 4 {
+            3 => format!("missing-{i}"),
+            _ => format!("apps-{i}"),
+        })
+        .collect();
+
+    c.bench_function("load theme", |b| {
+        b.iter(|| IconLookup::from_dirs(&theme, 48, &base_dirs, Vec::new()))
+    });
+
+    let lookup = IconLookup::from_dirs(&theme, 48, &base_dirs, Vec::new());
+    c.bench_function("find 200 icons", |b| {
+        b.iter(|| lookup.find_icons(names.iter().map(|name| name.as_str())))
+    });
+}
+
+criterion_group!(benches, icons_benchmark);
+criterion_main!(benches);
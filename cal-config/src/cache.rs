@@ -0,0 +1,140 @@
+//! On-disk cache of the parsed Freedesktop entries (with their resolved icons),
+//! invalidated when the directories they are loaded from change
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use serde::{Deserialize, Serialize};
+use walkdir::WalkDir;
+
+use crate::{
+    Config, Entry, cli::directories, exec::path_dirs, freedesktop::data_dirs, icons::base_dirs,
+};
+
+/// Name of the cache file, in the cache directory
+const CACHE: &str = "entries.json";
+
+/// Environment variables the entries depend on
+const ENV_VARS: &[&str] = &[
+    "LC_ALL",
+    "LC_MESSAGES",
+    "LANG",
+    "XDG_CURRENT_DESKTOP",
+    "XDG_DATA_HOME",
+    "XDG_DATA_DIRS",
+    "PATH",
+];
+
+/// Cached entries, with what they were computed from
+#[derive(Serialize, Deserialize)]
+struct Cache {
+    /// Config values and environment the entries depend on
+    key: Vec<Option<String>>,
+    /// State of the files and directories the entries were loaded from
+    sources: Vec<Source>,
+    /// Parsed entries
+    entries: Vec<Entry>,
+}
+
+/// State of a file or directory
+#[derive(Serialize, Deserialize, PartialEq)]
+struct Source {
+    path: PathBuf,
+    /// Modification time, in nanoseconds since the epoch
+    modified: u64,
+    size: u64,
+}
+
+impl Source {
+    fn new(path: &Path) -> Option<Self> {
+        let metadata = path.metadata().ok()?;
+        let modified = metadata
+            .modified()
+            .ok()?
+            .duration_since(UNIX_EPOCH)
+            .ok()?
+            .as_nanos() as u64;
+
+        Some(Self {
+            path: path.to_path_buf(),
+            modified,
+            size: metadata.len(),
+        })
+    }
+}
+
+/// Path to the cache file
+fn cache_path() -> Option<PathBuf> {
+    directories().map(|dirs| dirs.cache_dir().join(CACHE))
+}
+
+/// Config values and environment the entries depend on
+fn cache_key(config: &Config) -> Vec<Option<String>> {
+    let mut key = vec![
+        Some(env!("CARGO_PKG_VERSION").to_string()),
+        config.icon_theme.clone(),
+        Some(config.icon_size.to_string()),
+        config.locale.clone(),
+    ];
+    key.extend(ENV_VARS.iter().map(|var| env::var(var).ok()));
+    key
+}
+
+/// State of the directories the entries are loaded from:
+/// desktop files and their directories, icon theme directories and `$PATH` directories (for TryExec)
+fn sources() -> Vec<Source> {
+    let data_dirs = data_dirs();
+
+    let applications = data_dirs.iter().flat_map(|dir| {
+        WalkDir::new(dir.join("applications"))
+            .follow_links(true)
+            .into_iter()
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.into_path())
+    });
+
+    // Icon files are not listed: adding or removing one updates its directory
+    let icons = base_dirs().into_iter().flat_map(|dir| {
+        WalkDir::new(dir)
+            .follow_links(true)
+            .max_depth(3)
+            .into_iter()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().is_dir() || entry.file_name() == "index.theme")
+            .map(|entry| entry.into_path())
+    });
+
+    let pixmaps = data_dirs.iter().map(|dir| dir.join("pixmaps"));
+
+    applications
+        .chain(icons)
+        .chain(pixmaps)
+        .chain(path_dirs())
+        .filter_map(|path| Source::new(&path))
+        .collect()
+}
+
+/// Load the cached entries, if they are still up to date
+pub fn load(config: &Config) -> Option<Vec<Entry>> {
+    let content = fs::read_to_string(cache_path()?).ok()?;
+    let cache: Cache = serde_json::from_str(&content).ok()?;
+
+    (cache.key == cache_key(config) && cache.sources == sources()).then_some(cache.entries)
+}
+
+/// Store the entries in the cache (errors are ignored, the cache being optional)
+pub fn store(config: &Config, entries: &[Entry]) {
+    let cache = Cache {
+        key: cache_key(config),
+        sources: sources(),
+        entries: entries.to_vec(),
+    };
+
+    let _ = cache_path().and_then(|path| {
+        fs::create_dir_all(path.parent()?).ok()?;
+        fs::write(&path, serde_json::to_string(&cache).ok()?).ok()
+    });
+}
@@ -0,0 +1,412 @@
+//! Validation of the merged config and of the entries it declares (`cal check`)
+
+use std::{collections::HashMap, fmt, fs, ops::Range, path::PathBuf};
+
+use serde::Deserialize;
+use toml::Spanned;
+
+use crate::{
+    Config, Entry, Error, cli::Layer, entries::from_csv, exec::find_executable,
+    exec::parse_command, icons::IconLookup,
+};
+
+/// CSS color functions
+const COLOR_FUNCTIONS: &[&str] = &[
+    "rgb",
+    "rgba",
+    "hsl",
+    "hsla",
+    "hwb",
+    "lab",
+    "lch",
+    "oklab",
+    "oklch",
+    "color",
+    "color-mix",
+    "light-dark",
+    "var",
+];
+
+/// CSS named colors (and keywords), separated by spaces
+const NAMED_COLORS: &str = "\
+    transparent currentcolor aliceblue antiquewhite aqua aquamarine azure beige bisque \
+    black blanchedalmond blue blueviolet brown burlywood cadetblue chartreuse chocolate \
+    coral cornflowerblue cornsilk crimson cyan darkblue darkcyan darkgoldenrod darkgray \
+    darkgreen darkgrey darkkhaki darkmagenta darkolivegreen darkorange darkorchid darkred \
+    darksalmon darkseagreen darkslateblue darkslategray darkslategrey darkturquoise \
+    darkviolet deeppink deepskyblue dimgray dimgrey dodgerblue firebrick floralwhite \
+    forestgreen fuchsia gainsboro ghostwhite gold goldenrod gray green greenyellow grey \
+    honeydew hotpink indianred indigo ivory khaki lavender lavenderblush lawngreen \
+    lemonchiffon lightblue lightcoral lightcyan lightgoldenrodyellow lightgray lightgreen \
+    lightgrey lightpink lightsalmon lightseagreen lightskyblue lightslategray \
+    lightslategrey lightsteelblue lightyellow lime limegreen linen magenta maroon \
+    mediumaquamarine mediumblue mediumorchid mediumpurple mediumseagreen mediumslateblue \
+    mediumspringgreen mediumturquoise mediumvioletred midnightblue mintcream mistyrose \
+    moccasin navajowhite navy oldlace olive olivedrab orange orangered orchid palegoldenrod \
+    palegreen paleturquoise palevioletred papayawhip peachpuff peru pink plum powderblue \
+    purple rebeccapurple red rosybrown royalblue saddlebrown salmon sandybrown seagreen \
+    seashell sienna silver skyblue slateblue slategray slategrey snow springgreen steelblue \
+    tan teal thistle tomato turquoise violet wheat white whitesmoke yellow yellowgreen";
+
+/// Severity of a diagnostic
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Level {
+    /// Something that does not work
+    Error,
+    /// Something that may not work as expected
+    Warning,
+}
+
+/// Location of a diagnostic in a file
+#[derive(Debug, Clone)]
+pub struct Location {
+    pub path: PathBuf,
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+}
+
+impl Location {
+    /// Location of a whole file
+    fn file(path: PathBuf) -> Self {
+        Location {
+            path,
+            line: None,
+            column: None,
+        }
+    }
+}
+
+/// A problem found in the config
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub level: Level,
+    pub location: Option<Location>,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(location) = &self.location {
+            write!(f, "{}", location.path.display())?;
+            if let Some(line) = location.line {
+                write!(f, ":{line}")?;
+            }
+            if let Some(column) = location.column {
+                write!(f, ":{column}")?;
+            }
+            write!(f, ": ")?;
+        }
+
+        let level = match self.level {
+            Level::Error => "error",
+            Level::Warning => "warning",
+        };
+        write!(f, "{level}: {}", self.message)
+    }
+}
+
+impl From<Error> for Diagnostic {
+    fn from(err: Error) -> Self {
+        match err {
+            Error::Parse {
+                path,
+                line,
+                column,
+                message,
+            } => Diagnostic {
+                level: Level::Error,
+                location: Some(Location { path, line, column }),
+                message,
+            },
+            err => Diagnostic {
+                level: Level::Error,
+                location: None,
+                message: err.to_string(),
+            },
+        }
+    }
+}
+
+/// Check the merged config, given the config files it was merged from
+pub fn check(config: &Config, layers: &[Layer]) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+
+    // Colors, as named in the config files
+    if let Ok(serde_json::Value::Object(colors)) = serde_json::to_value(&config.colors) {
+        for (key, value) in colors {
+            let value = value.as_str().unwrap_or_default();
+            if !is_css_color(value) {
+                diagnostics.push(Diagnostic {
+                    level: Level::Error,
+                    location: origin(layers, &format!("/colors/{key}")).map(|file| {
+                        file.locate(file.positions.colors.get(&key).map(|value| value.span()))
+                    }),
+                    message: format!("invalid CSS color \"{value}\" for \"{key}\""),
+                });
+            }
+        }
+    }
+
+    let icons = IconLookup::new(&config.icon_theme, config.icon_size);
+    let mut names = HashMap::new();
+
+    let entries_origin = origin(layers, "/entries");
+    for (index, entry) in config.entries.iter().enumerate() {
+        let location = entries_origin.as_ref().map(|file| {
+            // Entries set elsewhere (e.g. by a profile) are not located
+            let span = file
+                .positions
+                .entries
+                .get(index)
+                .filter(|position| *position.name.get_ref() == entry.name)
+                .map(|position| position.name.span());
+            file.locate(span)
+        });
+        check_entry(entry, location, &icons, &mut names, &mut diagnostics);
+    }
+
+    if let Some(path) = &config.entries_file {
+        match from_csv(path) {
+            Ok(entries) => {
+                for entry in &entries {
+                    let location = Some(Location::file(path.clone()));
+                    check_entry(entry, location, &icons, &mut names, &mut diagnostics);
+                }
+            }
+            Err(err) => diagnostics.push(Diagnostic {
+                level: Level::Error,
+                location: Some(Location::file(path.clone())),
+                message: err.to_string(),
+            }),
+        }
+    }
+
+    diagnostics
+}
+
+/// Check an entry: its command must resolve on `$PATH`, its icon must exist
+/// and its name must be unique
+fn check_entry(
+    entry: &Entry,
+    location: Option<Location>,
+    icons: &IconLookup,
+    names: &mut HashMap<String, usize>,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let name = &entry.name;
+    let occurrence = *names
+        .entry(name.clone())
+        .and_modify(|count| *count += 1)
+        .or_default();
+
+    let mut report = |level: Level, message: String| {
+        diagnostics.push(Diagnostic {
+            level,
+            location: location.clone(),
+            message,
+        });
+    };
+
+    if occurrence > 0 {
+        report(Level::Warning, format!("duplicate entry name \"{name}\""));
+    }
+
+    // URLs are opened with the default handler
+    if entry.url.is_none() {
+        match parse_command(entry) {
+            Ok(args) => {
+                if find_executable(&args[0]).is_none() {
+                    report(
+                        Level::Error,
+                        format!("entry \"{name}\": \"{}\" not found on $PATH", args[0]),
+                    );
+                }
+            }
+            Err(err) => report(
+                Level::Error,
+                format!("entry \"{name}\": invalid command: {err}"),
+            ),
+        }
+    }
+
+    if let Some(icon) = &entry.icon
+        && icons.find_icon(icon).is_none()
+    {
+        report(
+            Level::Error,
+            format!("entry \"{name}\": icon \"{icon}\" not found"),
+        );
+    }
+}
+
+/// Positions of the checked values in a config file
+#[derive(Default, Deserialize)]
+struct Positions {
+    #[serde(default)]
+    entries: Vec<EntryPosition>,
+    #[serde(default)]
+    colors: HashMap<String, Spanned<toml::Value>>,
+}
+
+/// Position of an entry, at its name
+#[derive(Deserialize)]
+struct EntryPosition {
+    name: Spanned<String>,
+}
+
+/// A config file, with the positions of the values it sets.
+/// Only TOML files keep the positions of their values, the others are located by file.
+struct SourceFile {
+    path: PathBuf,
+    content: String,
+    positions: Positions,
+}
+
+impl SourceFile {
+    fn read(layer: &Layer) -> Self {
+        let content = fs::read_to_string(&layer.path).unwrap_or_default();
+        let positions = match layer.path.extension().is_some_and(|ext| ext == "toml") {
+            true if layer.colors => toml::from_str(&content).ok().map(|colors| Positions {
+                colors,
+                ..Default::default()
+            }),
+            true => toml::from_str(&content).ok(),
+            false => None,
+        };
+
+        Self {
+            path: layer.path.clone(),
+            content,
+            positions: positions.unwrap_or_default(),
+        }
+    }
+
+    /// Location of a value from its byte range (the file only, if it is unknown)
+    fn locate(&self, span: Option<Range<usize>>) -> Location {
+        let position = span
+            .and_then(|span| self.content.get(..span.start))
+            .map(|before| {
+                let line = before.matches('\n').count() + 1;
+                let column = before.chars().rev().take_while(|&c| c != '\n').count() + 1;
+                (line, column)
+            });
+
+        Location {
+            path: self.path.clone(),
+            line: position.map(|(line, _)| line),
+            column: position.map(|(_, column)| column),
+        }
+    }
+}
+
+/// Last config file (the one that wins) setting a key, as a JSON pointer
+fn origin(layers: &[Layer], pointer: &str) -> Option<SourceFile> {
+    layers
+        .iter()
+        .rev()
+        .find(|layer| layer.value.pointer(pointer).is_some())
+        .map(SourceFile::read)
+}
+
+/// Whether a value is a valid CSS color: hexadecimal, named, or a color function.
+/// Function arguments are not checked.
+fn is_css_color(value: &str) -> bool {
+    let value = value.trim().to_ascii_lowercase();
+
+    if let Some(hex) = value.strip_prefix('#') {
+        return matches!(hex.len(), 3 | 4 | 6 | 8) && hex.chars().all(|c| c.is_ascii_hexdigit());
+    }
+
+    if let Some((function, args)) = value.split_once('(') {
+        return COLOR_FUNCTIONS.contains(&function.trim_end())
+            && args
+                .strip_suffix(')')
+                .is_some_and(|args| !args.trim().is_empty() && balanced(args));
+    }
+
+    NAMED_COLORS.split_whitespace().any(|name| name == value)
+}
+
+/// Whether the parentheses of a string are balanced
+fn balanced(value: &str) -> bool {
+    let mut depth = 0usize;
+    for c in value.chars() {
+        match c {
+            '(' => depth += 1,
+            ')' => match depth.checked_sub(1) {
+                Some(d) => depth = d,
+                None => return false,
+            },
+            _ => {}
+        }
+    }
+    depth == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+    use crate::config::from_toml;
+
+    /// Check a config file, unique to the test
+    fn check_file(name: &str, content: &str) -> Vec<Diagnostic> {
+        let path = env::temp_dir().join(format!("cal-test-{}-{name}.toml", std::process::id()));
+        fs::write(&path, content).unwrap();
+
+        let layer = Layer {
+            path: path.clone(),
+            value: toml::from_str(content).unwrap(),
+            colors: false,
+        };
+        let diagnostics = check(&from_toml(content).unwrap(), &[layer]);
+
+        let _ = fs::remove_file(path);
+        diagnostics
+    }
+
+    /// Line and column of the diagnostics whose message contains some text
+    fn positions(diagnostics: &[Diagnostic], message: &str) -> Vec<(usize, usize)> {
+        diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.message.contains(message))
+            .filter_map(|diagnostic| diagnostic.location.as_ref())
+            .map(|location| (location.line.unwrap(), location.column.unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn locates_entries_by_name() {
+        let content = "placeholder = \"Apps here\"\n\
+                       \n\
+                       [[entries]]\n\
+                       name = \"A\"\n\
+                       command = \"sh\"\n\
+                       terminal = false\n\
+                       icon = \"/nonexistent/a.png\"\n\
+                       \n\
+                       [[entries]]\n\
+                       name = \"A\"\n\
+                       command = \"sh\"\n\
+                       terminal = false\n";
+
+        let diagnostics = check_file("entries", content);
+
+        assert_eq!(positions(&diagnostics, "icon"), vec![(4, 8)]);
+        assert_eq!(positions(&diagnostics, "duplicate"), vec![(10, 8)]);
+    }
+
+    #[test]
+    fn locates_colors() {
+        let content = "placeholder = \"red\"\n\
+                       \n\
+                       [colors]\n\
+                       textDim = \"red\"\n\
+                       accent = \"notacolor\"\n";
+
+        let diagnostics = check_file("colors", content);
+
+        assert_eq!(positions(&diagnostics, "notacolor"), vec![(5, 10)]);
+    }
+}
@@ -1,16 +1,26 @@
 //! CLI options for launching CAL
 
 use crate::{
-    Config,
-    colors::{self, Colors},
-    config::{DisplayMode, from_json, from_toml},
+    Config, Error,
+    check::{Diagnostic, check},
+    colors::Colors,
+    config::{DisplayMode, EntrySource},
+    schema::schema,
 };
-use clap::Parser;
+use clap::{Parser, Subcommand};
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
 use std::path::PathBuf;
-use std::{fs::read_to_string, path::Path};
+use std::{env, fs::read_to_string, path::Path};
+
+/// Supported config file extensions, by decreasing priority
+const EXTENSIONS: &[&str] = &["toml", "json", "yaml", "yml"];
+
+/// Config keys holding paths, relative to the config file setting them
+const PATH_KEYS: &[&str] = &["script", "entries_file"];
 
 /// Configurable App Launcher
-#[derive(Parser, Debug)]
+#[derive(Parser, Debug, Clone)]
 #[command(version, about, long_about = None)]
 struct Args {
     /// Path to another config file
@@ -21,6 +31,10 @@ struct Args {
     #[clap(short, long)]
     daemon: bool,
 
+    /// Dmenu mode: read entries from stdin and print the selection to stdout
+    #[clap(long)]
+    dmenu: bool,
+
     /// Icon theme name
     #[arg(short, long)]
     icon_theme: Option<String>,
@@ -32,118 +46,357 @@ struct Args {
     /// Menu display mode
     #[clap(short, long)]
     mode: Option<DisplayMode>,
+
+    /// Profile of the config to use (declared in `[profiles.<name>]`)
+    #[arg(long)]
+    mode_name: Option<String>,
+
+    /// Where the entries are loaded from, when none are given
+    #[arg(short, long)]
+    source: Option<EntrySource>,
+
+    /// Script printing the entries (implies the script source)
+    #[arg(long)]
+    script: Option<PathBuf>,
+
+    /// Only display the entries in one of these categories (comma separated)
+    #[arg(long, value_delimiter = ',')]
+    categories: Option<Vec<String>>,
+
+    /// CSV / TSV file to load the entries from
+    #[arg(short, long)]
+    entries: Option<PathBuf>,
+
+    /// Print the JSON Schema of the config files and exit
+    #[arg(long)]
+    print_schema: bool,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+enum Command {
+    /// Check the config files and the entries they declare, then exit
+    Check,
 }
 
 pub fn directories() -> Option<directories_next::ProjectDirs> {
     directories_next::ProjectDirs::from("com", "GnRlLeclerc", "cal")
 }
 
-/// Generate the config from CLI args and config files
-pub fn process_cli_config() -> Config {
+/// What the CLI args ask for
+#[derive(Debug)]
+pub enum CliAction {
+    /// Run the launcher with the config
+    Run(Box<Config>),
+    /// Print the JSON Schema of the config files
+    PrintSchema(String),
+    /// Print the problems found in the config files
+    Check(Vec<Diagnostic>),
+}
+
+/// Generate the config from CLI args and config files, unless the args ask for something else
+pub fn process_cli_config() -> Result<CliAction, Error> {
     let args = Args::parse();
-    let project_dirs = directories();
-    let cfg_dir = project_dirs.as_ref().map(|d| d.config_dir());
-
-    // In order of priority
-    // 1. Parse the config from the path specified in CLI args
-    // 2. Parse the config from any of the valid configuration files
-    // 3. Use the default config
-    let mut config = args
-        .config
-        // 1. CLI config path
-        .map(|cfg_path| read_config_from_path(&cfg_path))
-        // 2. Config from default paths
-        .or_else(|| {
-            cfg_dir.and_then(|cfg_dir| {
-                vec![cfg_dir.join("config.toml"), cfg_dir.join("config.json")]
-                    .iter()
-                    .filter(|p| p.exists())
-                    .next()
-                    .map(|p| read_config_from_path(&p))
-            })
-        })
-        // 3. Use the default config
-        .unwrap_or_else(|| from_toml("").unwrap());
-
-    // Parse the colors from colors.toml or colors.json, overriding the base config if found
-    let colors = cfg_dir.and_then(|cfg_dir| {
-        vec![cfg_dir.join("colors.toml"), cfg_dir.join("colors.json")]
-            .iter()
-            .filter(|p| p.exists())
-            .next()
-            .map(|p| read_colors_from_path(&p))
-    });
+
+    if args.print_schema {
+        return Ok(CliAction::PrintSchema(schema()));
+    }
+
+    if let Some(Command::Check) = args.command {
+        return Ok(CliAction::Check(check_config(&args)));
+    }
+
+    load_config(&args).map(|config| CliAction::Run(Box::new(config)))
+}
+
+/// Generate the config from CLI args and config files, ignoring the other actions
+/// (e.g. to reload the config of a running launcher)
+pub fn load_cli_config() -> Result<Config, Error> {
+    load_config(&Args::parse())
+}
+
+/// Directories containing the config files (to be watched for changes)
+pub fn config_dirs() -> Vec<PathBuf> {
+    let args = Args::parse();
+
+    layer_dirs()
+        .into_iter()
+        .chain(
+            args.config
+                .as_ref()
+                .and_then(|path| path.parent())
+                .map(|dir| dir.to_path_buf()),
+        )
+        .collect()
+}
+
+/// Config directories, by increasing priority: system directories ($XDG_CONFIG_DIRS),
+/// then the user config directory
+fn layer_dirs() -> Vec<PathBuf> {
+    let system_dirs = env::var("XDG_CONFIG_DIRS")
+        .ok()
+        .filter(|dirs| !dirs.is_empty())
+        .unwrap_or_else(|| "/etc/xdg".to_string());
+
+    let mut dirs: Vec<PathBuf> = system_dirs
+        .split(':')
+        .filter(|dir| !dir.is_empty())
+        .map(|dir| PathBuf::from(dir).join("cal"))
+        .collect();
+    // The first system directory has the highest priority
+    dirs.reverse();
+
+    dirs.extend(directories().map(|dirs| dirs.config_dir().to_path_buf()));
+    dirs
+}
+
+/// Find the first existing file with the given name and a supported extension
+fn find_file(dir: &Path, name: &str) -> Option<PathBuf> {
+    EXTENSIONS
+        .iter()
+        .map(|ext| dir.join(format!("{name}.{ext}")))
+        .find(|path| path.exists())
+}
+
+/// A config file, with the keys it sets (those of colors files are nested under `colors`)
+pub(crate) struct Layer {
+    pub path: PathBuf,
+    pub value: Value,
+    /// Whether this is a colors file
+    pub colors: bool,
+}
+
+/// Read the config files, by increasing priority:
+/// 1. Config and colors files of the system directories (`/etc/xdg/cal`)
+/// 2. Config and colors files of the user config directory
+/// 3. Config file specified in CLI args
+fn read_layers(args: &Args) -> Result<Vec<Layer>, Error> {
+    let mut layers = Vec::new();
+
+    for dir in layer_dirs() {
+        if let Some(path) = find_file(&dir, "config") {
+            let value = read_layer::<Config>(&path)?;
+            layers.push(Layer {
+                path,
+                value,
+                colors: false,
+            });
+        }
+        if let Some(path) = find_file(&dir, "colors") {
+            let colors = read_layer::<Colors>(&path)?;
+            let value = Value::Object(Map::from_iter([("colors".into(), colors)]));
+            layers.push(Layer {
+                path,
+                value,
+                colors: true,
+            });
+        }
+    }
+
+    if let Some(path) = &args.config {
+        let value = read_layer::<Config>(path)?;
+        layers.push(Layer {
+            path: path.clone(),
+            value,
+            colors: false,
+        });
+    }
+
+    Ok(layers)
+}
+
+/// Generate the config from parsed CLI args and config files.
+/// Layers are merged in order, each one overriding the keys it sets:
+/// the default config, then the config files (see `read_layers`), then the selected profile,
+/// then the CLI args.
+fn load_config(args: &Args) -> Result<Config, Error> {
+    merge_layers(args, &read_layers(args)?)
+}
+
+/// Merge the config files over the default config, then apply the selected profile and the CLI args
+fn merge_layers(args: &Args, layers: &[Layer]) -> Result<Config, Error> {
+    let mut merged = Value::Object(Map::new());
+    for layer in layers {
+        merge(&mut merged, resolve_paths(layer));
+    }
+
+    // The selected profile overrides the top-level keys of every layer
+    if let Some(name) = &args.mode_name {
+        let profile = merged
+            .get("profiles")
+            .and_then(|profiles| profiles.get(name))
+            .cloned()
+            .ok_or_else(|| Error::UnknownProfile { name: name.clone() })?;
+        merge(&mut merged, profile);
+    }
+
+    let mut config: Config = serde_json::from_value(merged).map_err(Error::Merge)?;
 
     // Override the config with CLI args
+    let args = args.clone();
     config.icon_theme = args.icon_theme.or(config.icon_theme);
-    config.daemon = args.daemon;
+    config.daemon = args.daemon || config.daemon;
+    config.dmenu = args.dmenu || config.dmenu;
     config.placeholder = args.placeholder.or(config.placeholder);
     config.mode = args.mode.unwrap_or(config.mode);
-    config.colors = colors.unwrap_or(config.colors);
+    config.source = args.source.unwrap_or(config.source);
+    if let Some(script) = args.script {
+        // Absolute, as the script may be run by the daemon from another directory
+        config.script = Some(script.canonicalize().unwrap_or(script));
+        config.source = EntrySource::Script;
+    }
+    config.categories = args.categories.or(config.categories);
+    config.entries_file = args.entries.or(config.entries_file);
+
+    Ok(config)
+}
+
+/// Resolve the relative paths of a config file (top-level and profile keys) from its directory,
+/// as cal may be launched from anywhere (e.g. a keybinding)
+fn resolve_paths(layer: &Layer) -> Value {
+    let mut value = layer.value.clone();
+    let path = match layer.path.canonicalize() {
+        Ok(path) => path,
+        Err(_) => return value,
+    };
+    let dir = path.parent().unwrap_or(&path);
+
+    let resolve = |table: &mut Value| {
+        for key in PATH_KEYS {
+            if let Some(Value::String(path)) = table.get_mut(*key)
+                && Path::new(path.as_str()).is_relative()
+            {
+                *path = dir.join(path.as_str()).to_string_lossy().to_string();
+            }
+        }
+    };
+
+    resolve(&mut value);
+    if let Some(Value::Object(profiles)) = value.get_mut("profiles") {
+        profiles.values_mut().for_each(resolve);
+    }
+
+    value
+}
 
-    config
+/// Check the config files and the entries they declare
+fn check_config(args: &Args) -> Vec<Diagnostic> {
+    match read_layers(args) {
+        Ok(layers) => match merge_layers(args, &layers) {
+            Ok(config) => check(&config, &layers),
+            Err(err) => vec![Diagnostic::from(err)],
+        },
+        Err(err) => vec![Diagnostic::from(err)],
+    }
+}
+
+/// Merge a config layer into the lower ones: tables are merged key by key,
+/// other values are replaced (null values are ignored)
+fn merge(base: &mut Value, layer: Value) {
+    match (base, layer) {
+        (_, Value::Null) => {}
+        (Value::Object(base), Value::Object(layer)) => {
+            for (key, value) in layer {
+                match base.get_mut(&key) {
+                    Some(base) => merge(base, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, layer) => *base = layer,
+    }
 }
 
 /// Read a file from a path
-fn read_from_path<P>(path: P) -> String
+fn read_from_path(path: &Path) -> Result<String, Error> {
+    read_to_string(path).map_err(|source| Error::Read {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+/// Get the extension of a config file
+fn extension(path: &Path) -> Result<&str, Error> {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .ok_or_else(|| Error::MissingExtension {
+            path: path.to_path_buf(),
+        })
+}
+
+/// Read a config layer from a file, keeping only the keys it sets.
+/// The file is first parsed as `T`, so that invalid values are reported with their location.
+fn read_layer<T>(path: &Path) -> Result<Value, Error>
 where
-    P: AsRef<Path>,
+    T: DeserializeOwned,
 {
-    read_to_string(path).expect("Failed to read config file")
+    parse_file::<T>(path)?;
+    parse_file::<Value>(path)
 }
 
-/// Read a config file from a path (util function)
-fn read_config_from_path<P>(path: P) -> Config
+/// Parse a TOML, JSON or YAML file, depending on its extension
+fn parse_file<T>(path: &Path) -> Result<T, Error>
 where
-    P: AsRef<Path>,
+    T: DeserializeOwned,
 {
-    let ext = path
-        .as_ref()
-        .extension()
-        .expect(&format!(
-            "Config file {} must have an extension",
-            path.as_ref().display(),
-        ))
-        .to_str()
-        .expect("Config file extension must be a string");
-
-    match ext {
-        "toml" => from_toml(&read_from_path(path)).unwrap(),
-        "json" => from_json(&read_from_path(path)).unwrap(),
-        "yaml" | "yml" => panic!("YAML config files are not supported yet"),
-        _ => {
-            panic!(
-                "Unsupported config file extension {} for file {}",
-                ext,
-                path.as_ref().display()
-            );
+    let extension = extension(path)?;
+    if !EXTENSIONS.contains(&extension) {
+        return Err(Error::UnsupportedExtension {
+            path: path.to_path_buf(),
+            extension: extension.to_string(),
+        });
+    }
+
+    let content = read_from_path(path)?;
+    match extension {
+        "toml" => {
+            toml::from_str(&content).map_err(|err| Error::toml(path.to_path_buf(), &content, err))
+        }
+        "json" => {
+            serde_json::from_str(&content).map_err(|err| Error::json(path.to_path_buf(), err))
         }
+        _ => serde_norway::from_str(&content).map_err(|err| Error::yaml(path.to_path_buf(), err)),
     }
 }
 
-fn read_colors_from_path<P>(path: P) -> Colors
-where
-    P: AsRef<Path>,
-{
-    let ext = path
-        .as_ref()
-        .extension()
-        .expect(&format!(
-            "Config file {} must have an extension",
-            path.as_ref().display(),
-        ))
-        .to_str()
-        .expect("Config file extension must be a string");
-
-    match ext {
-        "toml" => colors::from_toml(&read_from_path(path)).unwrap(),
-        "json" => colors::from_json(&read_from_path(path)).unwrap(),
-        _ => {
-            panic!(
-                "Unsupported config file extension {} for file {}",
-                ext,
-                path.as_ref().display()
-            );
-        }
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn resolves_paths_from_the_config_file() {
+        let dir = env::temp_dir().join(format!("cal-test-{}-paths", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.toml");
+        fs::write(&path, "").unwrap();
+
+        let layer = Layer {
+            path,
+            value: json!({
+                "entries_file": "bookmarks.csv",
+                "script": "/usr/bin/menu",
+                "profiles": { "power": { "script": "scripts/power.sh" } },
+            }),
+            colors: false,
+        };
+        let value = resolve_paths(&layer);
+
+        let dir = dir.canonicalize().unwrap();
+        let resolved = |path: &str| dir.join(path).to_string_lossy().to_string();
+        assert_eq!(value["entries_file"], resolved("bookmarks.csv"));
+        assert_eq!(value["script"], "/usr/bin/menu");
+        assert_eq!(
+            value["profiles"]["power"]["script"],
+            resolved("scripts/power.sh")
+        );
+        let _ = fs::remove_dir_all(dir);
     }
 }
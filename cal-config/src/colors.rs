@@ -1,9 +1,10 @@
 //! Color scheme configuration
 
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 /// Color scheme of the application
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct Colors {
     /// Background color
@@ -79,3 +80,8 @@ pub fn from_toml<'a>(config: &'a str) -> Result<Colors, toml::de::Error> {
 pub fn from_json(config: &str) -> serde_json::Result<Colors> {
     serde_json::from_str(config)
 }
+
+/// Parse colors from YAML
+pub fn from_yaml(config: &str) -> serde_norway::Result<Colors> {
+    serde_norway::from_str(config)
+}
@@ -1,10 +1,14 @@
 //! CAL config and how to parse it
 
+use std::{collections::HashMap, path::PathBuf};
+
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use crate::{Entry, colors::Colors};
 
-#[derive(Debug, Clone, Serialize, Deserialize, clap::ValueEnum)]
+/// How the entries are displayed
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, clap::ValueEnum)]
 #[serde(rename_all = "lowercase")]
 pub enum DisplayMode {
     /// Icon, title and description
@@ -17,16 +21,122 @@ pub enum DisplayMode {
     Compact,
 }
 
+/// Where the entries are loaded from, when none are given
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema, clap::ValueEnum,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum EntrySource {
+    /// Installed applications (desktop entries)
+    Desktop,
+    /// Executables of `$PATH`
+    Run,
+    /// Open windows (selecting one focuses it)
+    Window,
+    /// Hosts of the SSH config and known hosts (opened in a terminal)
+    Ssh,
+    /// Output of a rofi-style script (run again with the selected entry)
+    Script,
+    /// Entries of the combi sources, merged with the config entries
+    Combi,
+}
+
+impl EntrySource {
+    /// Whether the entries of the source can be cached by the daemon
+    /// (open windows change all the time and scripts depend on their state, they are run on demand,
+    /// and combi entries are merged from the other sources)
+    pub fn is_cached(&self) -> bool {
+        !matches!(
+            self,
+            EntrySource::Window | EntrySource::Script | EntrySource::Combi
+        )
+    }
+
+    /// Name of the source, as in the config (used to tag the combi entries)
+    pub fn name(&self) -> &'static str {
+        match self {
+            EntrySource::Desktop => "desktop",
+            EntrySource::Run => "run",
+            EntrySource::Window => "window",
+            EntrySource::Ssh => "ssh",
+            EntrySource::Script => "script",
+            EntrySource::Combi => "combi",
+        }
+    }
+}
+
+/// A source merged in combi mode
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct CombiSource {
+    /// Where the entries are loaded from (the config entries if omitted).
+    /// Neither the combi nor the script source can be merged.
+    pub source: Option<EntrySource>,
+    /// Prefix displayed before the entries, and typed to only search them (e.g. "ssh" or "!")
+    pub prefix: Option<String>,
+}
+
+/// How the desktop actions of an application (e.g. "New Private Window") are displayed
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ActionsMode {
+    /// Actions are not displayed
+    Hidden,
+    /// Actions are displayed as top-level entries, next to their application
+    Flat,
+    /// Actions are displayed on demand for the selected entry
+    Nested,
+}
+
+/// A named profile (e.g. a power menu), selected with `--mode-name`.
+/// The keys it sets override the top-level ones.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct Profile {
+    /// Search text placeholder
+    pub placeholder: Option<String>,
+
+    /// Menu display mode
+    pub mode: Option<DisplayMode>,
+
+    /// Where the entries are loaded from, when none are given
+    pub source: Option<EntrySource>,
+
+    /// Script printing the entries (script source), relative to the config file
+    pub script: Option<PathBuf>,
+
+    /// Only display the entries in one of these categories (e.g. "Game")
+    pub categories: Option<Vec<String>>,
+
+    /// The profile entries (replacing the top-level ones)
+    pub entries: Option<Vec<Entry>>,
+
+    /// CSV / TSV file to load additional entries from, relative to the config file
+    pub entries_file: Option<PathBuf>,
+
+    /// Colors (merged with the top-level ones)
+    pub colors: Option<Colors>,
+}
+
 /// The CAL config
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct Config {
     /// Daemon mode (does not display anything, runs a daemon)
     #[serde(default)]
     pub daemon: bool,
 
+    /// Dmenu mode (entries are read from stdin, the selection is printed to stdout)
+    #[serde(default)]
+    pub dmenu: bool,
+
     /// Icon theme name
     pub icon_theme: Option<String>,
 
+    /// Requested icon size, in pixels (the closest available size is used)
+    #[serde(default = "default_icon_size")]
+    pub icon_size: u32,
+
+    /// Locale used for the desktop entries translations (defaults to the system locale)
+    pub locale: Option<String>,
+
     /// Search text placeholder
     pub placeholder: Option<String>,
 
@@ -34,19 +144,93 @@ pub struct Config {
     #[serde(default = "default_display_mode")]
     pub mode: DisplayMode,
 
+    /// Where the entries are loaded from, when none are given
+    #[serde(default = "default_source")]
+    pub source: EntrySource,
+
+    /// Script printing the entries (script source), relative to the config file
+    pub script: Option<PathBuf>,
+
+    /// Sources merged in combi mode, in order
+    #[serde(default = "default_combi")]
+    pub combi: Vec<CombiSource>,
+
+    /// Desktop actions display mode
+    #[serde(default = "default_actions_mode")]
+    pub actions: ActionsMode,
+
+    /// Only display the entries in one of these categories (e.g. "Game")
+    pub categories: Option<Vec<String>>,
+
     /// The config entries
     #[serde(default)]
     pub entries: Vec<Entry>,
 
+    /// CSV / TSV file to load additional entries from, relative to the config file
+    pub entries_file: Option<PathBuf>,
+
     /// Colors (can be optionally provided through the config)
     #[serde(default)]
     pub colors: Colors,
+
+    /// Named profiles, by name
+    #[serde(default)]
+    pub profiles: HashMap<String, Profile>,
+}
+
+impl Config {
+    /// Whether the entries are loaded from the source: when none are given,
+    /// or in combi mode where they are merged with the given ones (never in dmenu mode)
+    pub fn loads_source(&self) -> bool {
+        !self.dmenu && (self.entries.is_empty() || self.source == EntrySource::Combi)
+    }
+
+    /// Whether the Freedesktop entries loaded with both configs are the same
+    /// (same translations and icons)
+    pub fn same_freedesktop(&self, other: &Config) -> bool {
+        self.locale == other.locale
+            && self.icon_theme == other.icon_theme
+            && self.icon_size == other.icon_size
+    }
 }
 
 fn default_display_mode() -> DisplayMode {
     DisplayMode::Full
 }
 
+fn default_source() -> EntrySource {
+    EntrySource::Desktop
+}
+
+fn default_combi() -> Vec<CombiSource> {
+    vec![
+        CombiSource {
+            source: None,
+            prefix: None,
+        },
+        CombiSource {
+            source: Some(EntrySource::Desktop),
+            prefix: None,
+        },
+        CombiSource {
+            source: Some(EntrySource::Run),
+            prefix: Some("!".to_string()),
+        },
+        CombiSource {
+            source: Some(EntrySource::Ssh),
+            prefix: Some("ssh".to_string()),
+        },
+    ]
+}
+
+fn default_icon_size() -> u32 {
+    48
+}
+
+fn default_actions_mode() -> ActionsMode {
+    ActionsMode::Nested
+}
+
 /// Parse a CAL config + entries from TOML
 pub fn from_toml<'a>(config: &'a str) -> Result<Config, toml::de::Error> {
     toml::from_str(config)
@@ -56,3 +240,8 @@ pub fn from_toml<'a>(config: &'a str) -> Result<Config, toml::de::Error> {
 pub fn from_json(config: &str) -> serde_json::Result<Config> {
     serde_json::from_str(config)
 }
+
+/// Parse a CAL config + entries from YAML
+pub fn from_yaml(config: &str) -> serde_norway::Result<Config> {
+    serde_norway::from_str(config)
+}
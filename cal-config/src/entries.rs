@@ -1,41 +1,547 @@
 //! CAL entries, and how to parse them
 
+use std::{
+    collections::{HashMap, HashSet},
+    fmt, fs,
+    io::BufRead,
+    path::{Path, PathBuf},
+};
+
+use clap::ValueEnum;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use crate::{freedesktop::freedesktop_entries, icons::load_icons};
+use crate::{
+    Config, cache,
+    config::{ActionsMode, EntrySource},
+    exec::{is_executable, path_dirs, quote},
+    freedesktop::{freedesktop_entries, freedesktop_entries_by_id},
+    icons::IconLookup,
+    script::run_script,
+    ssh::{SshHost, hosts_from_dir, ssh_dir},
+    windows::{detect, window_entries},
+};
 
 /// An entry in the app launcher
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, Default)]
 pub struct Entry {
+    /// Stable identifier (e.g. the desktop file ID), used instead of the name to store per-entry state
+    #[serde(default)]
+    pub id: Option<String>,
     /// Entry name
     pub name: String,
     /// Entry command (ran if the entry is selected)
     pub command: String,
+    /// URL opened with the default handler instead of running the command
+    #[serde(default)]
+    pub url: Option<String>,
     /// Optional entry picture
     pub icon: Option<String>,
     /// Optional entry description
     pub description: Option<String>,
     /// Keywords for filtering
     pub keywords: Option<Vec<String>>,
+    /// Optional generic name (e.g. "Web Browser"), used for filtering
+    #[serde(default)]
+    pub generic_name: Option<String>,
+    /// Optional categories (e.g. "Game"), used for filtering
+    #[serde(default)]
+    pub categories: Option<Vec<String>>,
+    /// Optional WM class of the entry windows
+    #[serde(default)]
+    pub startup_wm_class: Option<String>,
     /// Whether this entry should be executed within a new shell window
     pub terminal: bool,
+    /// Child actions (e.g. "New Private Window"), shown on demand
+    #[serde(default)]
+    pub actions: Vec<Entry>,
+    /// Desktop file the entry was loaded from (its command may then contain field codes)
+    #[serde(default)]
+    pub desktop_file: Option<PathBuf>,
+    /// Window focused instead of running the command (window source)
+    #[serde(default)]
+    pub window: Option<String>,
+    /// Data passed back to the script when the entry is selected (script source)
+    #[serde(default)]
+    pub info: Option<String>,
+    /// Source the entry was merged from, or "config" for the config entries (combi mode)
+    #[serde(default)]
+    pub source: Option<String>,
+    /// Prefix displayed before the entry name (combi mode)
+    #[serde(default)]
+    pub prefix: Option<String>,
+}
+
+impl Entry {
+    /// Whether the entry belongs to one of the given categories (case insensitive)
+    pub fn in_categories(&self, categories: &[String]) -> bool {
+        self.categories.as_ref().is_some_and(|own| {
+            own.iter()
+                .any(|category| categories.iter().any(|c| c.eq_ignore_ascii_case(category)))
+        })
+    }
+}
+
+/// Error while parsing CAL entries from a CSV / TSV file
+#[derive(Debug)]
+pub enum CsvError {
+    /// The file could not be read, or is not valid CSV
+    Csv(csv::Error),
+    /// A required column is missing from the header row
+    MissingColumn(&'static str),
+    /// A required cell is empty
+    EmptyValue { row: u64, column: &'static str },
+    /// A cell contains an invalid value
+    InvalidValue {
+        row: u64,
+        column: &'static str,
+        value: String,
+    },
+}
+
+impl fmt::Display for CsvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CsvError::Csv(err) => write!(f, "{err}"),
+            CsvError::MissingColumn(column) => write!(f, "missing column \"{column}\" in header"),
+            CsvError::EmptyValue { row, column } => {
+                write!(f, "row {row}, column \"{column}\": value cannot be empty")
+            }
+            CsvError::InvalidValue { row, column, value } => {
+                write!(
+                    f,
+                    "row {row}, column \"{column}\": invalid value \"{value}\""
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for CsvError {}
+
+impl From<csv::Error> for CsvError {
+    fn from(err: csv::Error) -> Self {
+        CsvError::Csv(err)
+    }
+}
+
+/// Parse CAL entries from a CSV file (or TSV, depending on the extension).
+/// The first row is a header naming the columns: `name` and `command` are required,
+/// `icon`, `description`, `keywords` (separated by `;`) and `terminal` are optional.
+pub fn from_csv<P>(path: P) -> Result<Vec<Entry>, CsvError>
+where
+    P: AsRef<Path>,
+{
+    let delimiter = match path.as_ref().extension().and_then(|ext| ext.to_str()) {
+        Some("tsv") => b'\t',
+        _ => b',',
+    };
+
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .trim(csv::Trim::All)
+        .from_path(path)?;
+
+    // Index the columns by name
+    let columns: HashMap<String, usize> = reader
+        .headers()?
+        .iter()
+        .enumerate()
+        .map(|(i, header)| (header.to_lowercase(), i))
+        .collect();
+
+    let required = |column: &'static str| {
+        columns
+            .get(column)
+            .copied()
+            .ok_or(CsvError::MissingColumn(column))
+    };
+    let name_column = required("name")?;
+    let command_column = required("command")?;
+
+    let mut entries = Vec::new();
+
+    for record in reader.records() {
+        let record = record?;
+        let row = record.position().map_or(0, |p| p.line());
+
+        // Get a cell by column name, empty cells being treated as missing
+        let cell = |column: &str| {
+            columns
+                .get(column)
+                .and_then(|&i| record.get(i))
+                .filter(|value| !value.is_empty())
+        };
+        let non_empty = |column: &'static str, i: usize| {
+            record
+                .get(i)
+                .filter(|value| !value.is_empty())
+                .map(|value| value.to_string())
+                .ok_or(CsvError::EmptyValue { row, column })
+        };
+
+        let terminal = match cell("terminal") {
+            None | Some("false") => false,
+            Some("true") => true,
+            Some(value) => {
+                return Err(CsvError::InvalidValue {
+                    row,
+                    column: "terminal",
+                    value: value.to_string(),
+                });
+            }
+        };
+
+        entries.push(Entry {
+            name: non_empty("name", name_column)?,
+            command: non_empty("command", command_column)?,
+            icon: cell("icon").map(|icon| icon.to_string()),
+            description: cell("description").map(|description| description.to_string()),
+            keywords: cell("keywords").map(|keywords| {
+                keywords
+                    .split(';')
+                    .map(|keyword| keyword.trim())
+                    .filter(|keyword| !keyword.is_empty())
+                    .map(|keyword| keyword.to_string())
+                    .collect()
+            }),
+            terminal,
+            ..Default::default()
+        });
+    }
+
+    Ok(entries)
+}
+
+/// Parse CAL entries from newline-separated items (dmenu mode).
+/// Items may be repeated: entries are identified by their line number.
+pub fn from_lines<R>(reader: R) -> Vec<Entry>
+where
+    R: BufRead,
+{
+    reader
+        .lines()
+        .map_while(Result::ok)
+        .enumerate()
+        .filter(|(_, line)| !line.is_empty())
+        .map(|(index, line)| Entry {
+            id: Some(format!("line:{}", index + 1)),
+            name: line,
+            ..Default::default()
+        })
+        .collect()
 }
 
-/// Parse CAL entries from CSV
-pub fn from_csv() -> Vec<Entry> {
-    todo!("CAL entries from CSV")
+/// Load the entries of a source
+pub fn from_source(config: &Config, source: EntrySource) -> Vec<Entry> {
+    match source {
+        EntrySource::Desktop => desktop_entries(from_freedesktop(config), config),
+        EntrySource::Run => from_executables(),
+        EntrySource::Window => from_windows(&from_freedesktop(config)),
+        EntrySource::Ssh => from_ssh(),
+        EntrySource::Script => from_script(config, None),
+        EntrySource::Combi => from_combi(config, |source| from_source(config, source)),
+    }
 }
 
-/// Parse CAL entries from Freedesktop ones
-pub fn from_freedesktop(icon_theme: &Option<String>) -> Vec<Entry> {
-    let icons = load_icons(icon_theme);
+/// Merge the config entries and those of the combi sources, tagged with their source and prefix.
+/// The entries of each source are loaded with `load` (e.g. from the daemon cache).
+pub fn from_combi(config: &Config, load: impl Fn(EntrySource) -> Vec<Entry>) -> Vec<Entry> {
+    config
+        .combi
+        .iter()
+        .flat_map(|combi| {
+            let (tag, entries) = match combi.source {
+                None => ("config", config.entries.clone()),
+                Some(EntrySource::Combi) => {
+                    eprintln!("The combi sources cannot include the combi source itself");
+                    return Vec::new();
+                }
+                // Script entries are only run by the script itself, in script mode
+                Some(EntrySource::Script) => {
+                    eprintln!("The combi sources cannot include the script source");
+                    return Vec::new();
+                }
+                Some(source) => (source.name(), load(source)),
+            };
+
+            entries
+                .into_iter()
+                .map(|entry| Entry {
+                    source: Some(tag.to_string()),
+                    prefix: combi.prefix.clone(),
+                    ..entry
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Load the entries of a source, as cached by the daemon
+/// (the actions of desktop entries are nested, see `desktop_entries`)
+pub fn from_cached_source(config: &Config, source: EntrySource) -> Vec<Entry> {
+    match source {
+        EntrySource::Desktop => from_freedesktop(config),
+        source => from_source(config, source),
+    }
+}
+
+/// Load the entries of every cached source (daemon only)
+pub fn from_sources(config: &Config) -> HashMap<EntrySource, Vec<Entry>> {
+    EntrySource::value_variants()
+        .iter()
+        .filter(|source| source.is_cached())
+        .map(|source| (*source, from_cached_source(config, *source)))
+        .collect()
+}
+
+/// List the executables of `$PATH` as entries, sorted by name.
+/// An executable shadows those with the same name in the next directories, hidden ones are skipped.
+pub fn from_executables() -> Vec<Entry> {
+    let mut executables: HashMap<String, PathBuf> = HashMap::new();
+
+    for dir in path_dirs() {
+        let files = match fs::read_dir(&dir) {
+            Ok(files) => files,
+            Err(_) => continue,
+        };
+
+        for path in files.filter_map(|file| file.ok()).map(|file| file.path()) {
+            let name = match path.file_name().and_then(|name| name.to_str()) {
+                // Hidden files are usually not meant to be run directly
+                Some(name) if !name.starts_with('.') && !executables.contains_key(name) => {
+                    name.to_string()
+                }
+                _ => continue,
+            };
+            if is_executable(&path) {
+                executables.insert(name, path);
+            }
+        }
+    }
 
-    freedesktop_entries()
+    let mut entries: Vec<Entry> = executables
         .into_iter()
-        .map(|(_, mut entry)| {
-            // Replace icon names with an actual existing file path
-            entry.icon = entry.icon.and_then(|icon| icons.get(&icon).cloned());
-            entry
+        .map(|(name, path)| Entry {
+            command: quote(&name),
+            description: Some(path.to_string_lossy().to_string()),
+            name,
+            ..Default::default()
+        })
+        .collect();
+    entries.sort_by(|a, b| a.name.cmp(&b.name));
+
+    entries
+}
+
+/// List the hosts of the user SSH config and known hosts as entries
+pub fn from_ssh() -> Vec<Entry> {
+    ssh_dir()
+        .map(|dir| hosts_from_dir(&dir).iter().map(SshHost::entry).collect())
+        .unwrap_or_default()
+}
+
+/// List the open windows as entries, with the icons of the given applications
+pub fn from_windows(applications: &[Entry]) -> Vec<Entry> {
+    let windows = match detect() {
+        Some(backend) => backend.windows(),
+        None => return Vec::new(),
+    };
+
+    match windows {
+        Ok(windows) => window_entries(windows, applications),
+        Err(err) => {
+            eprintln!("Failed to list the open windows: {err}");
+            Vec::new()
+        }
+    }
+}
+
+/// Run the configured script (again with the selected entry, if any),
+/// and parse the entries it prints with their icons resolved
+pub fn from_script(config: &Config, selection: Option<&Entry>) -> Vec<Entry> {
+    let script = match &config.script {
+        Some(script) => script,
+        None => {
+            eprintln!("No script configured for the script source");
+            return Vec::new();
+        }
+    };
+
+    match run_script(script, selection) {
+        Ok(mut entries) => {
+            resolve_icons(&mut entries, config);
+            entries
+        }
+        Err(err) => {
+            eprintln!("Failed to run the script {}: {err}", script.display());
+            Vec::new()
+        }
+    }
+}
+
+/// Parse CAL entries from Freedesktop ones (using the on-disk cache if it is up to date).
+/// Their desktop actions are nested and they are not filtered by category,
+/// see `desktop_entries` to display them as configured.
+pub fn from_freedesktop(config: &Config) -> Vec<Entry> {
+    cache::load(config).unwrap_or_else(|| {
+        let entries = load_freedesktop(config);
+        cache::store(config, &entries);
+        entries
+    })
+}
+
+/// Filter Freedesktop entries by category and display their desktop actions as configured
+/// (the daemon caches the entries, and applies the config of each request)
+pub fn desktop_entries(entries: Vec<Entry>, config: &Config) -> Vec<Entry> {
+    entries
+        .into_iter()
+        .filter(|entry| {
+            config
+                .categories
+                .as_ref()
+                .is_none_or(|categories| entry.in_categories(categories))
+        })
+        .flat_map(|mut entry| {
+            let actions = match config.actions {
+                ActionsMode::Hidden => {
+                    entry.actions.clear();
+                    Vec::new()
+                }
+                ActionsMode::Flat => std::mem::take(&mut entry.actions)
+                    .into_iter()
+                    .map(|action| Entry {
+                        name: format!("{} — {}", entry.name, action.name),
+                        description: entry.description.clone(),
+                        keywords: entry.keywords.clone(),
+                        generic_name: entry.generic_name.clone(),
+                        categories: entry.categories.clone(),
+                        startup_wm_class: entry.startup_wm_class.clone(),
+                        ..action
+                    })
+                    .collect(),
+                ActionsMode::Nested => Vec::new(),
+            };
+
+            std::iter::once(entry).chain(actions)
         })
         .collect()
 }
+
+/// Parse again the Freedesktop entries of some desktop file IDs (e.g. after their files changed),
+/// and replace them in entries loaded by `from_freedesktop`
+pub fn update_freedesktop(entries: &mut Vec<Entry>, ids: &HashSet<String>, config: &Config) {
+    let mut updated: Vec<Entry> = freedesktop_entries_by_id(ids, config.locale.as_deref())
+        .into_values()
+        .flatten()
+        .collect();
+    resolve_icons(&mut updated, config);
+
+    entries.retain(|entry| entry.id.as_ref().is_none_or(|id| !ids.contains(id)));
+    entries.extend(updated);
+    cache::store(config, entries);
+}
+
+/// Parse the Freedesktop entries and resolve their icons
+fn load_freedesktop(config: &Config) -> Vec<Entry> {
+    let mut entries: Vec<Entry> = freedesktop_entries(config.locale.as_deref())
+        .into_values()
+        .collect();
+    resolve_icons(&mut entries, config);
+    entries
+}
+
+/// Replace the icon names of entries and their actions with an actual existing file path
+fn resolve_icons(entries: &mut [Entry], config: &Config) {
+    // Only resolve the icons referenced by the entries
+    let icons = IconLookup::new(&config.icon_theme, config.icon_size).find_icons(
+        entries
+            .iter()
+            .flat_map(|entry| std::iter::once(entry).chain(&entry.actions))
+            .filter_map(|entry| entry.icon.as_deref()),
+    );
+
+    let resolve_icon = |entry: &mut Entry| {
+        entry.icon = entry.icon.take().and_then(|icon| icons.get(&icon).cloned());
+    };
+
+    for entry in entries {
+        resolve_icon(entry);
+        entry.actions.iter_mut().for_each(resolve_icon);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+
+    /// Parse a CSV / TSV file, unique to the test
+    fn parse(name: &str, content: &str) -> Result<Vec<Entry>, CsvError> {
+        let path = env::temp_dir().join(format!("cal-test-{}-{name}", std::process::id()));
+        fs::write(&path, content).unwrap();
+        let entries = from_csv(&path);
+        let _ = fs::remove_file(path);
+        entries
+    }
+
+    #[test]
+    fn parses_csv_entries() {
+        let content = "Name,Command,Icon,Keywords,Terminal\n\
+                       Htop, htop ,utilities-system-monitor, system; monitor ;,true\n\
+                       Files,nautilus,,,\n";
+
+        let entries = parse("entries.csv", content).unwrap();
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].name, "Htop");
+        assert_eq!(entries[0].command, "htop");
+        assert_eq!(entries[0].icon.as_deref(), Some("utilities-system-monitor"));
+        assert_eq!(
+            entries[0].keywords,
+            Some(vec!["system".to_string(), "monitor".to_string()])
+        );
+        assert!(entries[0].terminal);
+        // Empty optional cells are missing values
+        assert_eq!(entries[1].icon, None);
+        assert_eq!(entries[1].keywords, None);
+        assert!(!entries[1].terminal);
+    }
+
+    #[test]
+    fn detects_tsv_files() {
+        let content = "name\tcommand\tdescription\nDate\tdate +%H,%M\tCurrent time, in hours\n";
+
+        let entries = parse("entries.tsv", content).unwrap();
+
+        assert_eq!(entries[0].command, "date +%H,%M");
+        assert_eq!(
+            entries[0].description.as_deref(),
+            Some("Current time, in hours")
+        );
+    }
+
+    #[test]
+    fn csv_errors() {
+        assert!(matches!(
+            parse("missing.csv", "name,icon\nHtop,htop\n"),
+            Err(CsvError::MissingColumn("command"))
+        ));
+        assert!(matches!(
+            parse("empty.csv", "name,command\nHtop,htop\nFiles,\n"),
+            Err(CsvError::EmptyValue {
+                row: 3,
+                column: "command"
+            })
+        ));
+        assert!(matches!(
+            parse("terminal.csv", "name,command,terminal\nHtop,htop,yes\n"),
+            Err(CsvError::InvalidValue { row: 2, column: "terminal", value }) if value == "yes"
+        ));
+        assert!(matches!(
+            parse("columns.csv", "name,command\nHtop,htop,extra\n"),
+            Err(CsvError::Csv(_))
+        ));
+    }
+}
@@ -0,0 +1,154 @@
+//! Errors while loading the config files
+
+use std::{fmt, io, path::PathBuf};
+
+/// Error while loading a config or colors file
+#[derive(Debug)]
+pub enum Error {
+    /// The file could not be read
+    Read { path: PathBuf, source: io::Error },
+    /// The file has no extension to tell its format
+    MissingExtension { path: PathBuf },
+    /// The file format is not supported
+    UnsupportedExtension { path: PathBuf, extension: String },
+    /// The file is not valid (syntax error, unknown value...)
+    Parse {
+        path: PathBuf,
+        /// Line of the error (starting at 1), if known
+        line: Option<usize>,
+        /// Column of the error (starting at 1), if known
+        column: Option<usize>,
+        message: String,
+    },
+    /// The merged config layers are not a valid config
+    Merge(serde_json::Error),
+    /// The selected profile is not declared in the config
+    UnknownProfile { name: String },
+}
+
+impl Error {
+    /// Error from a TOML file, located from its content
+    pub fn toml(path: PathBuf, content: &str, err: toml::de::Error) -> Self {
+        let (line, column) = match err.span() {
+            Some(span) => {
+                let (line, column) = location(content, span.start);
+                (Some(line), Some(column))
+            }
+            None => (None, None),
+        };
+
+        Error::Parse {
+            path,
+            line,
+            column,
+            message: err.message().to_string(),
+        }
+    }
+
+    /// Error from a JSON file
+    pub fn json(path: PathBuf, err: serde_json::Error) -> Self {
+        // serde_json reports line 0 when the location is unknown
+        let known = err.line() > 0;
+        let message = err.to_string();
+        let suffix = format!(" at line {} column {}", err.line(), err.column());
+
+        Error::Parse {
+            path,
+            line: known.then(|| err.line()),
+            column: known.then(|| err.column()),
+            message: message
+                .strip_suffix(&suffix)
+                .unwrap_or(&message)
+                .to_string(),
+        }
+    }
+
+    /// Error from a YAML file
+    pub fn yaml(path: PathBuf, err: serde_norway::Error) -> Self {
+        let location = err.location();
+        let message = err.to_string();
+        let suffix = location
+            .as_ref()
+            .map(|location| format!(" at line {} column {}", location.line(), location.column()))
+            .unwrap_or_default();
+
+        Error::Parse {
+            path,
+            line: location.as_ref().map(|location| location.line()),
+            column: location.as_ref().map(|location| location.column()),
+            message: message
+                .strip_suffix(&suffix)
+                .unwrap_or(&message)
+                .to_string(),
+        }
+    }
+
+    /// Path of the file the error comes from, if any
+    pub fn path(&self) -> Option<&PathBuf> {
+        match self {
+            Error::Read { path, .. }
+            | Error::MissingExtension { path }
+            | Error::UnsupportedExtension { path, .. }
+            | Error::Parse { path, .. } => Some(path),
+            Error::Merge(_) | Error::UnknownProfile { .. } => None,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Read { path, source } => write!(f, "{}: {source}", path.display()),
+            Error::MissingExtension { path } => {
+                write!(f, "{}: missing file extension", path.display())
+            }
+            Error::UnsupportedExtension { path, extension } => {
+                write!(
+                    f,
+                    "{}: unsupported file extension \"{extension}\"",
+                    path.display()
+                )
+            }
+            Error::Parse {
+                path,
+                line,
+                column,
+                message,
+            } => {
+                write!(f, "{}", path.display())?;
+                if let Some(line) = line {
+                    write!(f, ":{line}")?;
+                }
+                if let Some(column) = column {
+                    write!(f, ":{column}")?;
+                }
+                write!(f, ": {message}")
+            }
+            Error::Merge(err) => write!(f, "invalid config: {err}"),
+            Error::UnknownProfile { name } => write!(f, "unknown profile \"{name}\""),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Read { source, .. } => Some(source),
+            Error::Merge(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// Line and column (starting at 1) of a byte offset in a text
+fn location(content: &str, offset: usize) -> (usize, usize) {
+    let before = content.get(..offset).unwrap_or(content);
+    let line = before.matches('\n').count() + 1;
+    let column = before
+        .rsplit('\n')
+        .next()
+        .map_or(0, |line| line.chars().count())
+        + 1;
+
+    (line, column)
+}
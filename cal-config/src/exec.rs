@@ -0,0 +1,369 @@
+//! Parsing of entry commands, following the Exec key rules of the Desktop Entry specification
+//! https://specifications.freedesktop.org/desktop-entry-spec/latest/exec-variables.html
+
+use std::{
+    env, fmt,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+};
+
+use crate::Entry;
+
+/// Error while parsing a command
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExecError {
+    /// The command has no program to run
+    Empty,
+    /// A quoted argument is never closed
+    UnterminatedQuote,
+    /// The command ends with a lone backslash
+    TrailingBackslash,
+}
+
+impl fmt::Display for ExecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExecError::Empty => write!(f, "empty command"),
+            ExecError::UnterminatedQuote => write!(f, "unterminated quoted argument"),
+            ExecError::TrailingBackslash => write!(f, "trailing backslash"),
+        }
+    }
+}
+
+impl std::error::Error for ExecError {}
+
+/// Values substituted to the field codes of a command
+pub struct FieldCodes<'a> {
+    /// Translated name of the application (`%c`)
+    pub name: &'a str,
+    /// Icon of the application (`%i`)
+    pub icon: Option<&'a str>,
+    /// Location of the desktop file (`%k`)
+    pub desktop_file: Option<&'a str>,
+}
+
+/// Split a command into arguments.
+/// Arguments are separated by spaces, and may be quoted with double quotes,
+/// in which case `"`, `` ` ``, `$` and `\` must be escaped with a backslash.
+pub fn tokenize(command: &str) -> Result<Vec<String>, ExecError> {
+    let mut args = Vec::new();
+    let mut arg = String::new();
+    // Whether an argument is being built (it may be an empty quoted string)
+    let mut in_arg = false;
+    let mut chars = command.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {
+                if in_arg {
+                    args.push(std::mem::take(&mut arg));
+                    in_arg = false;
+                }
+            }
+            '"' => {
+                in_arg = true;
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '`' | '$' | '\\')) => arg.push(c),
+                            // Not a reserved character: the backslash is kept as is
+                            Some(c) => {
+                                arg.push('\\');
+                                arg.push(c);
+                            }
+                            None => return Err(ExecError::UnterminatedQuote),
+                        },
+                        Some(c) => arg.push(c),
+                        None => return Err(ExecError::UnterminatedQuote),
+                    }
+                }
+            }
+            '\\' => {
+                in_arg = true;
+                match chars.next() {
+                    Some(c) => arg.push(c),
+                    None => return Err(ExecError::TrailingBackslash),
+                }
+            }
+            c => {
+                in_arg = true;
+                arg.push(c);
+            }
+        }
+    }
+
+    if in_arg {
+        args.push(arg);
+    }
+
+    match args.is_empty() {
+        true => Err(ExecError::Empty),
+        false => Ok(args),
+    }
+}
+
+/// Expand the field codes of tokenized arguments.
+/// No files or URLs are passed to the application, so their field codes are removed,
+/// as well as the deprecated ones. Unknown field codes are left untouched.
+pub fn expand_field_codes(args: Vec<String>, codes: &FieldCodes) -> Vec<String> {
+    let mut expanded = Vec::with_capacity(args.len());
+
+    for arg in args {
+        match arg.as_str() {
+            // Codes that expand to a list of arguments, or to nothing
+            "%f" | "%F" | "%u" | "%U" | "%d" | "%D" | "%n" | "%N" | "%v" | "%m" => {}
+            "%i" => {
+                if let Some(icon) = codes.icon {
+                    expanded.push("--icon".to_string());
+                    expanded.push(icon.to_string());
+                }
+            }
+            // Flatpak file forwarding markers
+            "@@" | "@@u" => {}
+            _ => expanded.push(expand_arg(&arg, codes)),
+        }
+    }
+
+    expanded
+}
+
+/// Expand the field codes embedded in a single argument
+fn expand_arg(arg: &str, codes: &FieldCodes) -> String {
+    let mut expanded = String::with_capacity(arg.len());
+    let mut chars = arg.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            expanded.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('%') => expanded.push('%'),
+            Some('c') => expanded.push_str(codes.name),
+            Some('k') => expanded.push_str(codes.desktop_file.unwrap_or_default()),
+            Some('f' | 'F' | 'u' | 'U' | 'd' | 'D' | 'n' | 'N' | 'v' | 'm' | 'i') => {}
+            Some(c) => {
+                expanded.push('%');
+                expanded.push(c);
+            }
+            None => expanded.push('%'),
+        }
+    }
+
+    expanded
+}
+
+/// Parse the command of an entry into a program and its arguments.
+/// Field codes are only expanded for entries loaded from desktop files.
+pub fn parse_command(entry: &Entry) -> Result<Vec<String>, ExecError> {
+    let args = tokenize(&entry.command)?;
+
+    let desktop_file = match &entry.desktop_file {
+        Some(desktop_file) => desktop_file.to_string_lossy(),
+        None => return Ok(args),
+    };
+
+    let codes = FieldCodes {
+        name: &entry.name,
+        icon: entry.icon.as_deref(),
+        desktop_file: Some(&desktop_file),
+    };
+
+    match expand_field_codes(args, &codes) {
+        args if args.is_empty() => Err(ExecError::Empty),
+        args => Ok(args),
+    }
+}
+
+/// Directories of `$PATH`, by decreasing priority
+pub fn path_dirs() -> Vec<PathBuf> {
+    env::var_os("PATH")
+        .map(|paths| env::split_paths(&paths).collect())
+        .unwrap_or_default()
+}
+
+/// Whether a path is an executable file
+pub fn is_executable(path: &Path) -> bool {
+    path.metadata()
+        .is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+}
+
+/// Find an executable program, either from its path or by searching `$PATH`
+pub fn find_executable(program: &str) -> Option<PathBuf> {
+    if program.contains('/') {
+        let path = PathBuf::from(program);
+        return is_executable(&path).then_some(path);
+    }
+
+    path_dirs()
+        .into_iter()
+        .map(|dir| dir.join(program))
+        .find(|path| is_executable(path))
+}
+
+/// Quote an argument so that `tokenize` reads it back as is
+pub fn quote(arg: &str) -> String {
+    let plain = !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_alphanumeric() || "-_.,:/+=@%".contains(c));
+
+    match plain {
+        true => arg.to_string(),
+        false => {
+            let mut quoted = String::with_capacity(arg.len() + 2);
+            quoted.push('"');
+            for c in arg.chars() {
+                if matches!(c, '"' | '`' | '$' | '\\') {
+                    quoted.push('\\');
+                }
+                quoted.push(c);
+            }
+            quoted.push('"');
+            quoted
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Entry loaded from a desktop file (field codes are expanded)
+    fn desktop_entry(command: &str, icon: Option<&str>) -> Entry {
+        Entry {
+            name: "Firefox".to_string(),
+            command: command.to_string(),
+            icon: icon.map(|icon| icon.to_string()),
+            desktop_file: Some(PathBuf::from("/usr/share/applications/firefox.desktop")),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn tokenizes_quoted_arguments() {
+        assert_eq!(
+            tokenize(r#"sh -c "foo bar""#).unwrap(),
+            vec!["sh", "-c", "foo bar"]
+        );
+        assert_eq!(
+            tokenize(r#"bash -c "echo \"\$HOME\" \\ \`date\`" """#).unwrap(),
+            vec!["bash", "-c", r#"echo "$HOME" \ `date`"#, ""]
+        );
+        // Backslashes before other characters are kept
+        assert_eq!(tokenize(r#"echo "a\nb""#).unwrap(), vec!["echo", r"a\nb"]);
+        assert_eq!(
+            tokenize(r"ls  my\ file   -l ").unwrap(),
+            vec!["ls", "my file", "-l"]
+        );
+    }
+
+    #[test]
+    fn tokenize_errors() {
+        assert_eq!(
+            tokenize(r#"sh -c "foo bar"#),
+            Err(ExecError::UnterminatedQuote)
+        );
+        assert_eq!(tokenize(r#"echo "foo\"#), Err(ExecError::UnterminatedQuote));
+        assert_eq!(tokenize(r"echo foo\"), Err(ExecError::TrailingBackslash));
+        assert_eq!(tokenize(""), Err(ExecError::Empty));
+        assert_eq!(tokenize("   "), Err(ExecError::Empty));
+    }
+
+    #[test]
+    fn expands_field_codes() {
+        assert_eq!(
+            parse_command(&desktop_entry("firefox %U", None)).unwrap(),
+            vec!["firefox"]
+        );
+        assert_eq!(
+            parse_command(&desktop_entry("gimp-2.10 %F", None)).unwrap(),
+            vec!["gimp-2.10"]
+        );
+        assert_eq!(
+            parse_command(&desktop_entry("printf 100%% --file=%f", None)).unwrap(),
+            vec!["printf", "100%", "--file="]
+        );
+        assert_eq!(
+            parse_command(&desktop_entry("app --name %c --desktop %k", None)).unwrap(),
+            vec![
+                "app",
+                "--name",
+                "Firefox",
+                "--desktop",
+                "/usr/share/applications/firefox.desktop"
+            ]
+        );
+        // Unknown field codes are left untouched
+        assert_eq!(
+            parse_command(&desktop_entry("app %z", None)).unwrap(),
+            vec!["app", "%z"]
+        );
+    }
+
+    #[test]
+    fn expands_icon_field_code() {
+        assert_eq!(
+            parse_command(&desktop_entry("kcalc %i", Some("accessories-calculator"))).unwrap(),
+            vec!["kcalc", "--icon", "accessories-calculator"]
+        );
+        assert_eq!(
+            parse_command(&desktop_entry("kcalc %i", None)).unwrap(),
+            vec!["kcalc"]
+        );
+    }
+
+    #[test]
+    fn removes_flatpak_forwarding() {
+        let command = "/usr/bin/flatpak run --branch=stable --arch=x86_64 --command=firefox \
+                       --file-forwarding org.mozilla.firefox @@u %U @@";
+
+        assert_eq!(
+            parse_command(&desktop_entry(command, None)).unwrap(),
+            vec![
+                "/usr/bin/flatpak",
+                "run",
+                "--branch=stable",
+                "--arch=x86_64",
+                "--command=firefox",
+                "--file-forwarding",
+                "org.mozilla.firefox"
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_command_errors() {
+        assert_eq!(
+            parse_command(&desktop_entry("%U", None)),
+            Err(ExecError::Empty)
+        );
+        assert_eq!(
+            parse_command(&desktop_entry(r#"sh -c "exit"#, None)),
+            Err(ExecError::UnterminatedQuote)
+        );
+    }
+
+    #[test]
+    fn config_commands_are_not_expanded() {
+        let entry = Entry {
+            name: "Date".to_string(),
+            command: "date +%H:%M".to_string(),
+            ..Default::default()
+        };
+
+        assert_eq!(parse_command(&entry).unwrap(), vec!["date", "+%H:%M"]);
+    }
+
+    #[test]
+    fn quoted_arguments_are_read_back() {
+        for arg in ["firefox", "my file", r#"say "hi" to $USER \o/"#, ""] {
+            assert_eq!(
+                tokenize(&format!("cmd {}", quote(arg))).unwrap(),
+                vec!["cmd", arg]
+            );
+        }
+    }
+}
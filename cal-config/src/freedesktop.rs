@@ -2,215 +2,434 @@
 //! Taken and adapted from https://github.com/FivEawE/desktopentries/blob/master/src/main.rs
 
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     env,
     io::{self, BufRead},
     path::{Path, PathBuf},
+    sync::Once,
 };
 
 use sys_locale::get_locale;
+use walkdir::WalkDir;
 
-use crate::Entry;
+use crate::{Entry, exec::find_executable};
 
-/// Get freedesktop entries (filtering out no display ones)
-pub fn freedesktop_entries() -> HashMap<String, Entry> {
-    // Get system locale for translations
-    let locale = get_locale();
-    let de = env::var("XDG_CURRENT_DESKTOP").ok();
+/// Get freedesktop entries by desktop file ID (filtering out no display ones).
+/// Translations use the given locale, or the system one.
+pub fn freedesktop_entries(locale: Option<&str>) -> HashMap<String, Entry> {
+    entries_from_dirs(&data_dirs(), &FreedesktopParser::from_env(locale), None)
+        .into_iter()
+        .filter_map(|(id, entry)| entry.map(|entry| (id, entry)))
+        .collect()
+}
 
-    let code: Option<String> = locale
-        .map(|l| l.split('-').next().map(|l| l.to_string()))
-        .flatten();
+/// Get the freedesktop entries of some desktop file IDs only (e.g. those whose files changed).
+/// IDs that are not displayed or no longer exist are mapped to None.
+pub fn freedesktop_entries_by_id(
+    ids: &HashSet<String>,
+    locale: Option<&str>,
+) -> HashMap<String, Option<Entry>> {
+    let mut entries = entries_from_dirs(
+        &data_dirs(),
+        &FreedesktopParser::from_env(locale),
+        Some(ids),
+    );
+    for id in ids {
+        entries.entry(id.clone()).or_insert(None);
+    }
+    entries
+}
 
-    let mut entries: HashMap<String, Entry> = HashMap::new();
-    let parser = FreedesktopParser::new(code, de);
+/// Get the freedesktop entries of data directories ordered by priority, optionally only some IDs.
+/// Hidden entries are kept as None, as they still shadow entries with the same ID.
+fn entries_from_dirs(
+    dirs: &[PathBuf],
+    parser: &FreedesktopParser,
+    ids: Option<&HashSet<String>>,
+) -> HashMap<String, Option<Entry>> {
+    let mut entries: HashMap<String, Option<Entry>> = HashMap::new();
 
-    let xdg_data_dirs = env::var("XDG_DATA_DIRS");
-    match xdg_data_dirs {
-        Ok(value) => {
-            for dir in value.split(':') {
-                let mut path = PathBuf::from(dir);
-                path.push("applications/");
-                add_entries_from_path(&path, &mut entries, &parser);
-            }
-        }
-        Err(_) => {
-            let base_path = "/usr/share/applications/";
-            eprintln!("$XDG_DATA_DIRS not set, defaulting to {}", base_path);
-            let path = Path::new(base_path);
-            add_entries_from_path(&path, &mut entries, &parser);
-        }
-    };
+    // The first entry found for an ID wins
+    for dir in dirs {
+        add_entries_from_path(&dir.join("applications"), &mut entries, parser, ids);
+    }
 
     entries
 }
 
-/// Add all entries from a given path to the entries vector
+/// XDG data directories, by decreasing priority ($XDG_DATA_HOME, then $XDG_DATA_DIRS)
+pub(crate) fn data_dirs() -> Vec<PathBuf> {
+    let data_home = env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")));
+
+    let data_dirs = env::var("XDG_DATA_DIRS")
+        .ok()
+        .filter(|dirs| !dirs.is_empty())
+        .unwrap_or_else(|| {
+            // The data directories are listed many times (e.g. on each daemon reload)
+            static WARNING: Once = Once::new();
+            WARNING.call_once(|| {
+                eprintln!("$XDG_DATA_DIRS not set, defaulting to /usr/local/share:/usr/share");
+            });
+            "/usr/local/share:/usr/share".to_string()
+        });
+
+    data_home
+        .into_iter()
+        .chain(
+            data_dirs
+                .split(':')
+                .filter(|dir| !dir.is_empty())
+                .map(PathBuf::from),
+        )
+        .collect()
+}
+
+/// Compute the desktop file ID of a file relative to its `applications` directory
+/// (subdirectories are joined with `-`, e.g. `kde/foo.desktop` becomes `kde-foo.desktop`)
+pub(crate) fn desktop_file_id(applications_dir: &Path, path: &Path) -> Option<String> {
+    let relative = path.strip_prefix(applications_dir).ok()?;
+    let components = relative
+        .components()
+        .map(|component| component.as_os_str().to_str())
+        .collect::<Option<Vec<_>>>()?;
+
+    Some(components.join("-"))
+}
+
+/// Add all entries from an `applications` directory (and its subdirectories)
+/// that do not shadow an already known desktop file ID (optionally only some IDs)
 fn add_entries_from_path(
     path: &Path,
-    entries: &mut HashMap<String, Entry>,
+    entries: &mut HashMap<String, Option<Entry>>,
     parser: &FreedesktopParser,
+    ids: Option<&HashSet<String>>,
 ) {
-    if let Ok(dir_entries) = path.read_dir() {
-        dir_entries
-            .filter_map(|entry| entry.ok())
-            .filter(|entry| {
-                let path = entry.path();
-                path.is_file() && path.extension().map_or(false, |ext| ext == "desktop")
-            })
-            .for_each(|file| {
-                let path = file.path();
+    WalkDir::new(path)
+        .follow_links(true)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            let path = entry.path();
+            path.is_file() && path.extension().map_or(false, |ext| ext == "desktop")
+        })
+        .for_each(|file| {
+            let file_path = file.path();
 
-                let _ = parser.parse(&path, entries).inspect_err(|_| {
-                    eprintln!("Failed to parse file: {:?}", path);
-                });
-            });
+            let id = match desktop_file_id(path, file_path) {
+                Some(id)
+                    if !entries.contains_key(&id) && ids.is_none_or(|ids| ids.contains(&id)) =>
+                {
+                    id
+                }
+                _ => return,
+            };
+
+            match parser.parse(file_path, &id) {
+                Ok(entry) => {
+                    entries.insert(id, entry);
+                }
+                Err(_) => eprintln!("Failed to parse file: {:?}", file_path),
+            }
+        });
+}
+
+/// Get the user locale, in the POSIX format (`lang_COUNTRY.ENCODING@MODIFIER`) if possible
+fn user_locale() -> Option<String> {
+    ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .find_map(|var| env::var(var).ok().filter(|value| !value.is_empty()))
+        .or_else(get_locale)
+}
+
+/// Locale suffixes to look up for localized keys, by decreasing priority
+/// (`lang_COUNTRY@MODIFIER`, `lang_COUNTRY`, `lang@MODIFIER`, then `lang`).
+/// The encoding is ignored, and BCP 47 tags (`pt-BR`) are accepted as well.
+fn locale_variants(locale: &str) -> Vec<String> {
+    let (locale, modifier) = match locale.split_once('@') {
+        Some((locale, modifier)) => (locale, Some(modifier)),
+        None => (locale, None),
+    };
+    let locale = locale.split('.').next().unwrap_or_default().replace('-', "_");
+    let (lang, country) = match locale.split_once('_') {
+        Some((lang, country)) => (lang, Some(country)),
+        None => (locale.as_str(), None),
+    };
+
+    if lang.is_empty() || lang == "C" || lang == "POSIX" {
+        return Vec::new();
+    }
+
+    let mut variants = Vec::new();
+    if let (Some(country), Some(modifier)) = (country, modifier) {
+        variants.push(format!("{lang}_{country}@{modifier}"));
+    }
+    if let Some(country) = country {
+        variants.push(format!("{lang}_{country}"));
+    }
+    if let Some(modifier) = modifier {
+        variants.push(format!("{lang}@{modifier}"));
+    }
+    variants.push(lang.to_string());
+
+    variants
+}
+
+/// Key-value pairs of a group in a Freedesktop file
+type Group = HashMap<String, String>;
+
+/// Read the groups of a Freedesktop file by name (e.g. `Desktop Entry`), with unescaped values
+fn read_groups(path: &Path) -> io::Result<HashMap<String, Group>> {
+    let mut groups: HashMap<String, Group> = HashMap::new();
+    let mut current: Option<String> = None;
+
+    let file = std::fs::File::open(path)?;
+    let reader = io::BufReader::new(file);
+
+    for line in reader.lines() {
+        let line = line?;
+        let line = line.trim();
+
+        // Ignore comments
+        if line.starts_with('#') {
+            continue;
+        }
+
+        // Switch groups when a header is found
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            current = Some(name.to_string());
+            groups.entry(name.to_string()).or_default();
+            continue;
+        }
+
+        let (key, value) = match line.split_once('=') {
+            Some((key, value)) => (key.trim(), value.trim()),
+            None => continue,
+        };
+
+        if value.is_empty() {
+            continue;
+        }
+
+        if let Some(group) = current.as_ref().and_then(|name| groups.get_mut(name)) {
+            group.insert(key.to_string(), unescape(value));
+        }
     }
+
+    Ok(groups)
 }
 
 struct FreedesktopParser {
-    /// Precomputed key for the locale name
-    locale_name_key: Option<String>,
-    /// Precomputed key for the locale comment
-    locale_comment_key: Option<String>,
+    /// Locale suffixes for localized keys, by decreasing priority
+    locales: Vec<String>,
     /// Desktop environment (to be checked against "NotShowIn")
     desktop_environment: Option<String>,
 }
 
 impl FreedesktopParser {
-    pub fn new(locale_code: Option<String>, desktop_environment: Option<String>) -> Self {
-        let locale_name_key = locale_code.as_ref().map(|code| format!("Name[{code}]"));
-        let locale_comment_key = locale_code.as_ref().map(|code| format!("Comment[{code}]"));
+    pub fn new(locale: Option<String>, desktop_environment: Option<String>) -> Self {
         Self {
-            locale_name_key,
-            locale_comment_key,
+            locales: locale.map(|l| locale_variants(&l)).unwrap_or_default(),
             desktop_environment,
         }
     }
 
-    /// Parse a Freedesktop file and add the entries to the list
-    pub fn parse(&self, path: &Path, entries: &mut HashMap<String, Entry>) -> io::Result<()> {
-        let mut entry = Entry::default();
-        let mut valid = false;
+    /// Parser for the given locale (or the system one) and the current desktop environment
+    fn from_env(locale: Option<&str>) -> Self {
+        let locale = locale.map(|l| l.to_string()).or_else(user_locale);
+        Self::new(locale, env::var("XDG_CURRENT_DESKTOP").ok())
+    }
 
-        let file = std::fs::File::open(path)?;
-        let reader = io::BufReader::new(file);
+    /// Parse a Freedesktop file into an entry (None if it should not be displayed).
+    /// Its desktop actions are parsed into the entry's child actions.
+    pub fn parse(&self, path: &Path, id: &str) -> io::Result<Option<Entry>> {
+        let groups = read_groups(path)?;
 
-        for line in reader.lines() {
-            let line = line?;
-            let line = line.trim();
+        let group = match groups.get("Desktop Entry") {
+            Some(group) => group,
+            None => return Ok(None),
+        };
+        let value = |key: &str| group.get(key).map(|value| value.as_str());
 
-            // Ignore comments
-            if line.starts_with('#') {
-                continue;
-            }
+        if value("NoDisplay") == Some("true") || value("Hidden") == Some("true") {
+            return Ok(None);
+        }
 
-            // Start a new entry if the header is found
-            if line == "[Desktop Entry]" {
-                let e = entry;
-                entry = Entry::default(); // Reset for the new entry
-                if valid {
-                    entries.insert(e.name.clone(), e);
-                }
-                valid = true;
-                continue;
-            } else if line.starts_with("[") {
-                let e = entry;
-                entry = Entry::default(); // Reset for the new entry
-                if valid {
-                    entries.insert(e.name.clone(), e);
-                }
-                // This is not a desktop entry, but an action, etc
-                valid = false;
+        if let Some(de) = self.desktop_environment.as_ref() {
+            let de = de.to_lowercase();
+            if value("NotShowIn").is_some_and(|value| value.to_lowercase().contains(&de))
+                || value("OnlyShowIn").is_some_and(|value| !value.to_lowercase().contains(&de))
+            {
+                return Ok(None);
             }
+        }
 
-            if !valid {
-                continue;
+        let mut entry = Entry {
+            id: Some(id.to_string()),
+            desktop_file: Some(path.to_path_buf()),
+            terminal: value("Terminal") == Some("true"),
+            generic_name: self.localized(group, "GenericName").map(|s| s.to_string()),
+            categories: value("Categories").map(split_list),
+            startup_wm_class: value("StartupWMClass").map(|s| s.to_string()),
+            ..self.entry(group)
+        };
+
+        if entry.name.is_empty() {
+            return Ok(None);
+        }
+
+        // Links are opened with the default handler, other types are not launchable
+        match value("Type") {
+            None | Some("Application") => {}
+            Some("Link") if value("URL").is_some() => {
+                entry.command.clear();
+                entry.url = value("URL").map(|url| url.to_string());
             }
+            Some(_) => return Ok(None),
+        }
 
-            let mut values = line.split('=');
-            let key = values.next();
-            let value = values.collect::<Vec<&str>>().join("=");
+        // Skip applications that are not installed
+        if value("TryExec").is_some_and(|program| find_executable(program).is_none()) {
+            return Ok(None);
+        }
 
-            let key = match key {
-                Some(key) => key,
-                None => continue,
-            };
+        // Attach the declared actions, in order. They inherit the entry icon if they have none.
+        entry.actions = value("Actions")
+            .unwrap_or_default()
+            .split(';')
+            .filter(|action_id| !action_id.is_empty())
+            .filter_map(|action_id| {
+                let group = groups.get(&format!("Desktop Action {action_id}"))?;
+                let action = self.entry(group);
 
-            let value = match value.is_empty() {
-                false => value,
-                true => continue,
-            };
+                (!action.name.is_empty() && !action.command.is_empty()).then(|| Entry {
+                    id: Some(format!("{id}:{action_id}")),
+                    icon: action.icon.clone().or_else(|| entry.icon.clone()),
+                    terminal: entry.terminal,
+                    desktop_file: entry.desktop_file.clone(),
+                    ..action
+                })
+            })
+            .collect();
 
-            // Compare locale key
-            if let Some(name_key) = self.locale_name_key.as_ref() {
-                if key == name_key {
-                    entry.name = value.to_string();
-                }
-            }
-            // Compare locale comment key
-            if let Some(comment_key) = self.locale_comment_key.as_ref() {
-                if key == comment_key {
-                    entry.description = Some(value.to_string());
-                }
-            }
+        Ok(Some(entry))
+    }
 
-            // Match other predefined keys
-            match key {
-                "Name" => {
-                    if entry.name.is_empty() {
-                        entry.name = value.to_string();
-                    }
-                }
-                "Exec" => entry.command = value.to_string(),
-                "Comment" => {
-                    if entry.command.is_empty() {
-                        entry.command = value.to_string();
-                    }
-                }
-                "Keywords" => {
-                    entry.keywords = Some(
-                        value
-                            .split(';')
-                            .filter(|s| !s.is_empty())
-                            .map(|s| s.to_string())
-                            .collect(),
-                    );
-                }
-                "Icon" => entry.icon = Some(value.to_string()),
-                "NoDisplay" => {
-                    if value == "true" {
-                        valid = false;
-                    }
-                }
-                "NotShowIn" => {
-                    if let Some(de) = self.desktop_environment.as_ref() {
-                        if value.to_lowercase().contains(&de.to_lowercase()) {
-                            valid = false;
-                        }
-                    }
-                }
-                "OnlyShowIn" => {
-                    if let Some(de) = self.desktop_environment.as_ref() {
-                        if !value.to_lowercase().contains(&de.to_lowercase()) {
-                            valid = false;
-                        }
-                    }
-                }
-                "Terminal" => {
-                    if value == "true" {
-                        entry.terminal = true;
-                    }
-                }
-                _ => {}
-            }
+    /// Build an entry from the keys shared by desktop entries and desktop actions
+    fn entry(&self, group: &Group) -> Entry {
+        Entry {
+            name: self.localized(group, "Name").unwrap_or_default().to_string(),
+            command: group.get("Exec").cloned().unwrap_or_default(),
+            icon: group.get("Icon").cloned(),
+            description: self.localized(group, "Comment").map(|s| s.to_string()),
+            keywords: self.localized(group, "Keywords").map(split_list),
+            ..Default::default()
+        }
+    }
+
+    /// Get the value of a localized key, following the locale matching order
+    fn localized<'a>(&self, group: &'a Group, key: &str) -> Option<&'a str> {
+        self.locales
+            .iter()
+            .find_map(|locale| group.get(&format!("{key}[{locale}]")))
+            .or_else(|| group.get(key))
+            .map(|value| value.as_str())
+    }
+}
+
+/// Split a list value (separated by `;`)
+fn split_list(value: &str) -> Vec<String> {
+    value
+        .split(';')
+        .filter(|s| !s.is_empty())
+        .map(|s| s.to_string())
+        .collect()
+}
+
+/// Unescape a string value (`\s`, `\n`, `\t`, `\r` and `\\`).
+/// Other escape sequences are left untouched, as they may be meaningful to the key
+/// (e.g. `\;` in lists or quoting in Exec).
+fn unescape(value: &str) -> String {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
         }
 
-        // Add the last entry
-        if valid {
-            entries.insert(entry.name.clone(), entry);
+        match chars.peek() {
+            Some('s') => unescaped.push(' '),
+            Some('n') => unescaped.push('\n'),
+            Some('t') => unescaped.push('\t'),
+            Some('r') => unescaped.push('\r'),
+            Some('\\') => unescaped.push('\\'),
+            _ => {
+                unescaped.push('\\');
+                continue;
+            }
         }
+        chars.next();
+    }
+
+    unescaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Entries of the fixture data directories (`high` has priority over `low`)
+    fn fixture_entries() -> HashMap<String, Entry> {
+        let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/desktop");
+        let dirs = [fixtures.join("high"), fixtures.join("low")];
+
+        entries_from_dirs(&dirs, &FreedesktopParser::new(None, None), None)
+            .into_iter()
+            .filter_map(|(id, entry)| entry.map(|entry| (id, entry)))
+            .collect()
+    }
+
+    #[test]
+    fn skips_missing_try_exec() {
+        let entries = fixture_entries();
+
+        assert!(!entries.contains_key("missing.desktop"));
+        assert_eq!(entries["installed.desktop"].name, "Installed");
+    }
+
+    #[test]
+    fn hidden_entries_shadow_lower_priority_ones() {
+        assert!(!fixture_entries().contains_key("hidden.desktop"));
+    }
+
+    #[test]
+    fn links_open_their_url() {
+        let entries = fixture_entries();
+        let link = &entries["link.desktop"];
+
+        assert_eq!(link.name, "Documentation");
+        assert_eq!(link.url.as_deref(), Some("https://example.org/docs"));
+        assert_eq!(link.command, "");
+    }
+
+    #[test]
+    fn skips_directories() {
+        assert!(!fixture_entries().contains_key("directory.desktop"));
+    }
+
+    #[test]
+    fn subdirectories_are_joined_in_ids() {
+        let entries = fixture_entries();
+        let mut ids: Vec<&str> = entries.keys().map(|id| id.as_str()).collect();
+        ids.sort();
 
-        Ok(())
+        assert_eq!(
+            ids,
+            vec!["installed.desktop", "kde-editor.desktop", "link.desktop"]
+        );
     }
 }
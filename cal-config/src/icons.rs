@@ -1,158 +1,292 @@
-//! Indexation of application icons at launch to replace them by their paths
+//! Resolution of application icon names to their paths, following the Icon Theme Specification
+//! https://specifications.freedesktop.org/icon-theme-spec/latest/
 
 use std::{
     collections::HashMap,
-    env,
-    ffi::OsStr,
+    env, fs,
     path::{Path, PathBuf},
 };
 
-use walkdir::WalkDir;
-
-/// Index desktop icons paths by name
-pub fn load_icons(theme: &Option<String>) -> HashMap<String, String> {
-    // NOTE: icon paths contain their resolution in their values.
-    // SVGs have the maximum resolution possible.
-    // The tuples represent (resolution, is_fallback, path)
-    let mut icon_paths: HashMap<String, (usize, bool, String)> = HashMap::new();
-    let icon_dirs = &["icons/hicolor", "pixmaps"];
-
-    let xdg_data_dirs = env::var("XDG_DATA_DIRS");
-    match xdg_data_dirs {
-        Ok(value) => {
-            for dir in value.split(':') {
-                if let Some(theme) = &theme {
-                    let mut base_path = PathBuf::from(dir);
-                    base_path.push("icons");
-                    base_path.push(theme);
-                    process_icons_dir(&base_path, false, &mut icon_paths);
-                }
-
-                let path = PathBuf::from(dir);
-                icon_dirs.iter().for_each(|icon_dir| {
-                    process_icons_dir(&path.join(icon_dir), true, &mut icon_paths);
-                });
-            }
+use crate::freedesktop::data_dirs;
+
+/// Theme used as a last resort, which every theme implicitly inherits from
+const FALLBACK_THEME: &str = "hicolor";
+
+/// Supported icon file extensions, by decreasing priority
+const EXTENSIONS: &[&str] = &["png", "svg", "xpm"];
+
+/// Icon lookup for a theme and an icon size.
+/// Themes are loaded once, then icons are resolved on demand
+/// by probing the candidate directories directly.
+pub struct IconLookup {
+    /// Existing icon directories of each theme in the inheritance chain,
+    /// sorted by distance to the requested size
+    themes: Vec<Vec<PathBuf>>,
+    /// Directories of unthemed icons
+    pixmaps: Vec<PathBuf>,
+}
+
+impl IconLookup {
+    /// Load the icon theme (and its parents) from the XDG directories
+    pub fn new(theme: &Option<String>, size: u32) -> Self {
+        let pixmaps = data_dirs().into_iter().map(|dir| dir.join("pixmaps")).collect();
+        Self::from_dirs(theme, size, &base_dirs(), pixmaps)
+    }
+
+    /// Load the icon theme (and its parents) from the given base directories
+    pub fn from_dirs(
+        theme: &Option<String>,
+        size: u32,
+        base_dirs: &[PathBuf],
+        pixmaps: Vec<PathBuf>,
+    ) -> Self {
+        let themes = theme_chain(theme, base_dirs)
+            .into_iter()
+            .map(|theme| {
+                let mut dirs: Vec<(u32, PathBuf)> = theme
+                    .dirs
+                    .iter()
+                    .flat_map(|dir| {
+                        let distance = dir.size_distance(size, 1);
+                        theme
+                            .base_dirs
+                            .iter()
+                            .map(move |base_dir| (distance, base_dir.join(&dir.path)))
+                    })
+                    .filter(|(_, path)| path.is_dir())
+                    .collect();
+
+                // Stable sort: directories of equal distance keep the theme order
+                dirs.sort_by_key(|(distance, _)| *distance);
+                dirs.into_iter().map(|(_, path)| path).collect()
+            })
+            .collect();
+
+        let pixmaps = pixmaps.into_iter().filter(|dir| dir.is_dir()).collect();
+
+        Self { themes, pixmaps }
+    }
+
+    /// Find the path of an icon by name, in the closest size available.
+    /// Absolute paths are returned as is if they exist.
+    pub fn find_icon(&self, name: &str) -> Option<PathBuf> {
+        if Path::new(name).is_absolute() {
+            return Some(PathBuf::from(name)).filter(|path| path.is_file());
         }
-        Err(_) => {
-            eprintln!("$XDG_DATA_DIRS not set, defaulting to /usr/share/icons");
 
-            if let Some(theme) = theme {
-                let mut base_path = PathBuf::from("/usr/share/icons");
-                base_path.push(theme);
-                process_icons_dir(&base_path, false, &mut icon_paths);
-            }
+        // The first theme of the chain that provides the icon wins
+        self.themes
+            .iter()
+            .chain(std::iter::once(&self.pixmaps))
+            .find_map(|dirs| dirs.iter().find_map(|dir| probe_icon(dir, name)))
+    }
+
+    /// Resolve a list of icon names (each name is looked up once)
+    pub fn find_icons<'a, I>(&self, names: I) -> HashMap<String, String>
+    where
+        I: IntoIterator<Item = &'a str>,
+    {
+        let mut icons = HashMap::new();
 
-            let base_path = PathBuf::from("/usr/share");
-            icon_dirs.iter().for_each(|icon_dir| {
-                process_icons_dir(&base_path.join(icon_dir), true, &mut icon_paths);
-            });
+        for name in names {
+            if icons.contains_key(name) {
+                continue;
+            }
+            if let Some(path) = self.find_icon(name) {
+                icons.insert(name.to_string(), path.to_string_lossy().to_string());
+            }
         }
+
+        icons
     }
+}
 
-    icon_paths.into_iter().map(|(k, v)| (k, v.2)).collect()
+/// Look for an icon in a directory, trying every supported extension
+fn probe_icon(dir: &Path, name: &str) -> Option<PathBuf> {
+    EXTENSIONS
+        .iter()
+        .map(|ext| dir.join(format!("{name}.{ext}")))
+        .find(|path| path.is_file())
 }
 
-fn process_icons_dir(
-    path: &Path,
-    is_fallback: bool,
-    mut icon_paths: &mut HashMap<String, (usize, bool, String)>,
-) {
-    if path.exists() {
-        if path.iter().last() == Some(OsStr::new("pixmaps")) {
-            insert_icons_with_weight(path, 0, is_fallback, icon_paths);
-            return;
+/// Base directories containing icon themes, by decreasing priority
+pub(crate) fn base_dirs() -> Vec<PathBuf> {
+    env::var_os("HOME")
+        .map(|home| PathBuf::from(home).join(".icons"))
+        .into_iter()
+        .chain(data_dirs().into_iter().map(|dir| dir.join("icons")))
+        .collect()
+}
+
+/// Load a theme and the themes it inherits from (depth first), ending with the fallback theme
+fn theme_chain(theme: &Option<String>, base_dirs: &[PathBuf]) -> Vec<IconTheme> {
+    let mut chain: Vec<IconTheme> = Vec::new();
+    let mut pending: Vec<String> = theme.iter().cloned().collect();
+    pending.push(FALLBACK_THEME.to_string());
+    pending.reverse();
+
+    while let Some(name) = pending.pop() {
+        if chain.iter().any(|theme| theme.name == name) {
+            continue;
         }
 
-        match path.read_dir() {
-            Ok(entries) => entries
-                .into_iter()
-                .filter_map(|entry| entry.ok())
-                .filter(|entry| entry.path().is_dir())
-                .for_each(|entry| {
-                    // Analyze each directory for the resolution
-                    let path = entry.path();
-                    let last_dir = path
-                        .file_name()
-                        .and_then(|name| name.to_str())
-                        .unwrap_or("unknown");
-
-                    match last_dir {
-                        "symbolic" => return,
-                        "scalable" => {
-                            insert_icons_with_weight(
-                                &path,
-                                usize::MAX,
-                                is_fallback,
-                                &mut icon_paths,
-                            );
-                        }
-                        _ => {
-                            let resolution = last_dir
-                                .split('x')
-                                .next()
-                                .and_then(|s| s.parse::<usize>().ok())
-                                .unwrap_or(0);
-                            insert_icons_with_weight(
-                                &path,
-                                resolution,
-                                is_fallback,
-                                &mut icon_paths,
-                            );
-                        }
-                    }
-                }),
-            Err(_) => {
-                eprintln!("Error reading directory: {}", path.display());
-            }
+        if let Some(theme) = IconTheme::load(&name, base_dirs) {
+            // Parents are explored before the remaining themes
+            pending.extend(theme.inherits.iter().rev().cloned());
+            chain.push(theme);
         }
     }
+
+    chain
 }
 
-/// Recursively iterate over a path to load all icons.
-/// These icons correspond to a specific resolution.
-/// If an icon is already in the map, it is only overwritten if the resolution is higher.
-fn insert_icons_with_weight(
-    path: &Path,
-    weight: usize,
-    is_fallback: bool,
-    icon_paths: &mut HashMap<String, (usize, bool, String)>,
-) {
-    for entry in WalkDir::new(path) {
-        match entry {
-            Ok(entry) => {
-                if entry.path().is_file() {
-                    // Get the filename to get the icon it belongs to
-                    if let Some(icon) = basename(entry.file_name()) {
-                        icon_paths
-                            .entry(icon.to_string())
-                            .and_modify(|v| {
-                                if (v.0 < weight && is_fallback <= v.1) || (v.1 > is_fallback) {
-                                    v.0 = weight;
-                                    v.1 = is_fallback;
-                                    v.2 = entry.path().to_string_lossy().to_string();
-                                }
-                            })
-                            .or_insert((
-                                weight,
-                                is_fallback,
-                                entry.path().to_string_lossy().to_string(),
-                            ));
-                    }
-                }
-            }
-            Err(_) => eprintln!("Error reading directory: {}", path.display()),
-        }
+/// An icon theme, as described by its `index.theme` file
+struct IconTheme {
+    /// Theme (directory) name
+    name: String,
+    /// Existing theme directories in the base directories, by decreasing priority
+    base_dirs: Vec<PathBuf>,
+    /// Icon subdirectories
+    dirs: Vec<ThemeDir>,
+    /// Names of the parent themes
+    inherits: Vec<String>,
+}
+
+impl IconTheme {
+    /// Load a theme from the first `index.theme` found in the base directories
+    fn load(name: &str, base_dirs: &[PathBuf]) -> Option<Self> {
+        let base_dirs: Vec<PathBuf> = base_dirs
+            .iter()
+            .map(|dir| dir.join(name))
+            .filter(|dir| dir.is_dir())
+            .collect();
+
+        let index = base_dirs
+            .iter()
+            .find_map(|dir| fs::read_to_string(dir.join("index.theme")).ok())?;
+        let groups = parse_index(&index);
+        let theme = groups.get("Icon Theme")?;
+
+        let list = |key: &str| -> Vec<String> {
+            theme
+                .get(key)
+                .map(|value| {
+                    value
+                        .split(',')
+                        .map(|s| s.trim())
+                        .filter(|s| !s.is_empty())
+                        .map(|s| s.to_string())
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+
+        let mut dir_names = list("Directories");
+        dir_names.extend(list("ScaledDirectories"));
+
+        let dirs = dir_names
+            .into_iter()
+            .filter_map(|path| {
+                let group = groups.get(&path)?;
+                ThemeDir::new(path, group)
+            })
+            .collect();
+
+        Some(Self {
+            name: name.to_string(),
+            base_dirs,
+            dirs,
+            inherits: list("Inherits"),
+        })
     }
 }
 
-/// Returns the basename of a file.
-fn basename(filename: &OsStr) -> Option<&str> {
-    filename.to_str().map(|s| {
-        if let Some(last_dot_index) = s.rfind('.') {
-            &s[..last_dot_index]
-        } else {
-            s
+/// Type of an icon theme subdirectory
+enum DirType {
+    /// Icons of exactly one size
+    Fixed,
+    /// Icons that can be scaled between a minimum and a maximum size
+    Scalable { min_size: u32, max_size: u32 },
+    /// Icons that can be used for sizes within a threshold
+    Threshold { threshold: u32 },
+}
+
+/// A subdirectory of an icon theme
+struct ThemeDir {
+    /// Path relative to the theme directory
+    path: String,
+    /// Nominal icon size
+    size: u32,
+    /// Target scale of the icons
+    scale: u32,
+    /// How the icons of the directory can be resized
+    dir_type: DirType,
+}
+
+impl ThemeDir {
+    /// Parse a subdirectory from its `index.theme` group (None if its size is missing)
+    fn new(path: String, group: &HashMap<String, String>) -> Option<Self> {
+        let number = |key: &str| group.get(key).and_then(|value| value.parse::<u32>().ok());
+
+        let size = number("Size")?;
+        let dir_type = match group.get("Type").map(|s| s.as_str()) {
+            Some("Fixed") => DirType::Fixed,
+            Some("Scalable") => DirType::Scalable {
+                min_size: number("MinSize").unwrap_or(size),
+                max_size: number("MaxSize").unwrap_or(size),
+            },
+            _ => DirType::Threshold {
+                threshold: number("Threshold").unwrap_or(2),
+            },
+        };
+
+        Some(Self {
+            path,
+            size,
+            scale: number("Scale").unwrap_or(1),
+            dir_type,
+        })
+    }
+
+    /// Distance between the directory icons size and the requested size (0 if it matches)
+    fn size_distance(&self, size: u32, scale: u32) -> u32 {
+        let (size, scale) = (size * scale, self.scale);
+
+        let (min, max) = match self.dir_type {
+            DirType::Fixed => (self.size, self.size),
+            DirType::Scalable { min_size, max_size } => (min_size, max_size),
+            DirType::Threshold { threshold } => (
+                self.size.saturating_sub(threshold),
+                self.size.saturating_add(threshold),
+            ),
+        };
+
+        (min * scale).saturating_sub(size) + size.saturating_sub(max * scale)
+    }
+}
+
+/// Parse the groups of an `index.theme` file
+fn parse_index(content: &str) -> HashMap<String, HashMap<String, String>> {
+    let mut groups: HashMap<String, HashMap<String, String>> = HashMap::new();
+    let mut current: Option<String> = None;
+
+    for line in content.lines().map(|line| line.trim()) {
+        if line.starts_with('#') {
+            continue;
         }
-    })
+
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            current = Some(name.to_string());
+            continue;
+        }
+
+        if let (Some(group), Some((key, value))) = (&current, line.split_once('=')) {
+            groups
+                .entry(group.clone())
+                .or_default()
+                .insert(key.trim().to_string(), value.trim().to_string());
+        }
+    }
+
+    groups
 }
@@ -1,11 +1,22 @@
 //! Configurable App Launcher entries
 
+mod cache;
+mod check;
 pub mod cli;
 pub mod colors;
 pub mod config;
 pub mod entries;
+mod error;
+pub mod exec;
 mod freedesktop;
-mod icons;
+pub mod icons;
+pub mod schema;
+pub mod script;
+pub mod ssh;
+pub mod watch;
+pub mod windows;
 
+pub use check::{Diagnostic, Level};
 pub use config::Config;
 pub use entries::Entry;
+pub use error::Error;
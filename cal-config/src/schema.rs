@@ -0,0 +1,11 @@
+//! JSON Schema of the CAL config, generated from its types
+
+use schemars::schema_for;
+
+use crate::Config;
+
+/// JSON Schema of the config files (pretty-printed), as committed in `schema.json`
+pub fn schema() -> String {
+    let schema = schema_for!(Config);
+    serde_json::to_string_pretty(&schema).expect("Failed to serialize the config schema") + "\n"
+}
@@ -0,0 +1,87 @@
+//! Rofi-style scripts (script source): the script prints the entries, one per line,
+//! and is run again with the selected entry as argument
+//! https://davatorium.github.io/rofi/current/rofi-script.5/
+
+use std::{
+    io,
+    path::Path,
+    process::{Command, Stdio},
+};
+
+use crate::Entry;
+
+/// Separator between an entry and its row options
+const OPTIONS_SEPARATOR: char = '\0';
+
+/// Separator between the row option keys and values
+const FIELD_SEPARATOR: char = '\x1f';
+
+/// Run a script, initially or with the selected entry, and parse the entries it prints.
+/// As in rofi, `$ROFI_RETV` tells whether an entry was selected, and `$ROFI_INFO` is set to its info.
+pub fn run_script(script: &Path, selection: Option<&Entry>) -> io::Result<Vec<Entry>> {
+    let mut command = Command::new(script);
+    command.stdin(Stdio::null()).stderr(Stdio::inherit());
+
+    match selection {
+        Some(entry) => {
+            command.arg(&entry.name).env("ROFI_RETV", "1");
+            if let Some(info) = &entry.info {
+                command.env("ROFI_INFO", info);
+            }
+        }
+        None => {
+            command.env("ROFI_RETV", "0");
+        }
+    }
+
+    let output = command.output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "{} exited with {}",
+            script.display(),
+            output.status
+        )));
+    }
+
+    Ok(parse_script_output(&String::from_utf8_lossy(
+        &output.stdout,
+    )))
+}
+
+/// Parse the output of a script: one entry per line, optionally followed by `\0`
+/// and row options (`key\x1fvalue`, separated by `\x1f`): `icon`, `meta` (search keywords),
+/// `info` (passed back to the script) and `nonselectable` (the row is skipped).
+/// Mode options (lines starting with `\0`) are not supported and skipped.
+pub fn parse_script_output(output: &str) -> Vec<Entry> {
+    output
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with(OPTIONS_SEPARATOR))
+        .filter_map(|(index, line)| {
+            let (name, options) = line.split_once(OPTIONS_SEPARATOR).unwrap_or((line, ""));
+
+            let mut entry = Entry {
+                // Lines may be repeated, their number identifies them
+                id: Some(format!("line:{}", index + 1)),
+                name: name.to_string(),
+                ..Default::default()
+            };
+
+            let mut fields = options.split(FIELD_SEPARATOR);
+            while let (Some(key), Some(value)) = (fields.next(), fields.next()) {
+                match key {
+                    "icon" => entry.icon = Some(value.to_string()),
+                    "meta" => {
+                        entry.keywords =
+                            Some(value.split_whitespace().map(|s| s.to_string()).collect())
+                    }
+                    "info" => entry.info = Some(value.to_string()),
+                    "nonselectable" if value == "true" => return None,
+                    _ => {}
+                }
+            }
+
+            Some(entry)
+        })
+        .collect()
+}
@@ -0,0 +1,245 @@
+//! SSH hosts, from the OpenSSH client config and known hosts (ssh source)
+
+use std::{
+    collections::HashSet,
+    env, fs,
+    path::{Component, Path, PathBuf},
+};
+
+use crate::{Entry, exec::quote};
+
+/// Maximum depth of nested `Include` directives (as in OpenSSH)
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+/// A host to connect to
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SshHost {
+    /// Host name or alias
+    pub host: String,
+    /// Non-default port (from `[host]:port` known hosts)
+    pub port: Option<u16>,
+}
+
+impl SshHost {
+    /// Entry connecting to the host in a terminal
+    pub fn entry(&self) -> Entry {
+        let (name, command) = match self.port {
+            Some(port) => (
+                format!("{}:{port}", self.host),
+                format!("ssh -p {port} {}", quote(&self.host)),
+            ),
+            None => (self.host.clone(), format!("ssh {}", quote(&self.host))),
+        };
+
+        Entry {
+            id: Some(format!("ssh:{name}")),
+            name,
+            command,
+            keywords: Some(vec!["ssh".to_string()]),
+            terminal: true,
+            ..Default::default()
+        }
+    }
+}
+
+/// User SSH directory (`~/.ssh`)
+pub fn ssh_dir() -> Option<PathBuf> {
+    env::var_os("HOME").map(|home| PathBuf::from(home).join(".ssh"))
+}
+
+/// Hosts of the `config` and `known_hosts` files of an SSH directory, without duplicates.
+/// Hosts of the config come first, in order.
+pub fn hosts_from_dir(ssh_dir: &Path) -> Vec<SshHost> {
+    let known_hosts = fs::read_to_string(ssh_dir.join("known_hosts")).unwrap_or_default();
+
+    let mut seen = HashSet::new();
+    config_hosts(&ssh_dir.join("config"), ssh_dir)
+        .into_iter()
+        .chain(known_hosts_hosts(&known_hosts))
+        .filter(|host| seen.insert(host.clone()))
+        .collect()
+}
+
+/// Hosts declared by the `Host` directives of a client config file, following `Include` directives.
+/// Wildcard and negated patterns are skipped, as well as duplicates.
+/// Relative includes are resolved from the SSH directory.
+pub fn config_hosts(path: &Path, ssh_dir: &Path) -> Vec<SshHost> {
+    let mut hosts = Vec::new();
+    read_config(path, ssh_dir, 0, &mut hosts);
+
+    let mut seen = HashSet::new();
+    hosts.retain(|host| seen.insert(host.clone()));
+    hosts
+}
+
+/// Read the hosts of a config file (and the files it includes)
+fn read_config(path: &Path, ssh_dir: &Path, depth: usize, hosts: &mut Vec<SshHost>) {
+    if depth > MAX_INCLUDE_DEPTH {
+        eprintln!("Too many nested SSH config includes in {}", path.display());
+        return;
+    }
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(_) => return,
+    };
+
+    for line in content.lines().map(|line| line.trim()) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        // Keywords are case insensitive, and separated from their arguments by spaces or `=`
+        let (keyword, args) = match line.split_once(|c: char| c.is_whitespace() || c == '=') {
+            Some((keyword, args)) => (
+                keyword,
+                args.trim_start_matches(|c: char| c.is_whitespace() || c == '='),
+            ),
+            None => continue,
+        };
+
+        match keyword.to_lowercase().as_str() {
+            "host" => hosts.extend(
+                split_args(args)
+                    .into_iter()
+                    .filter(|pattern| !pattern.contains(['*', '?', '!']))
+                    .map(|host| SshHost { host, port: None }),
+            ),
+            "include" => {
+                for pattern in split_args(args) {
+                    for include in expand_include(&pattern, ssh_dir) {
+                        read_config(&include, ssh_dir, depth + 1, hosts);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Split the arguments of a directive (separated by spaces, possibly double quoted)
+fn split_args(args: &str) -> Vec<String> {
+    let mut split = Vec::new();
+    let mut arg = String::new();
+    let mut quoted = false;
+
+    for c in args.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            c if c.is_whitespace() && !quoted => {
+                if !arg.is_empty() {
+                    split.push(std::mem::take(&mut arg));
+                }
+            }
+            c => arg.push(c),
+        }
+    }
+    if !arg.is_empty() {
+        split.push(arg);
+    }
+
+    split
+}
+
+/// Expand an `Include` pattern into the matching files, sorted.
+/// `~` is the home directory, relative paths are relative to the SSH directory.
+fn expand_include(pattern: &str, ssh_dir: &Path) -> Vec<PathBuf> {
+    let pattern = match pattern.strip_prefix("~/") {
+        Some(rest) => match env::var_os("HOME") {
+            Some(home) => PathBuf::from(home).join(rest),
+            None => return Vec::new(),
+        },
+        None => ssh_dir.join(pattern),
+    };
+
+    let mut paths = vec![PathBuf::new()];
+    for component in pattern.components() {
+        let name = match component {
+            Component::Normal(name) => name.to_string_lossy(),
+            other => {
+                paths.iter_mut().for_each(|path| path.push(other));
+                continue;
+            }
+        };
+
+        paths = match name.contains(['*', '?']) {
+            false => paths.into_iter().map(|path| path.join(&*name)).collect(),
+            true => paths
+                .into_iter()
+                .flat_map(|dir| {
+                    let mut matches: Vec<PathBuf> = fs::read_dir(&dir)
+                        .into_iter()
+                        .flatten()
+                        .filter_map(|file| file.ok())
+                        .filter(|file| wildcard_match(&name, &file.file_name().to_string_lossy()))
+                        .map(|file| file.path())
+                        .collect();
+                    matches.sort();
+                    matches
+                })
+                .collect(),
+        };
+    }
+
+    paths.into_iter().filter(|path| path.is_file()).collect()
+}
+
+/// Match a name against a pattern with `*` (any characters) and `?` (one character) wildcards
+fn wildcard_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+
+    // Backtracking on the last `*`
+    let (mut p, mut n) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(c) if *c == '?' || *c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((star_p, star_n)) => {
+                    p = star_p + 1;
+                    n = star_n + 1;
+                    star = Some((star_p, star_n + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/// Hosts of a `known_hosts` file.
+/// Hashed host names (`|1|...`) cannot be recovered and are skipped,
+/// as well as patterns and `@cert-authority` / `@revoked` lines.
+pub fn known_hosts_hosts(content: &str) -> Vec<SshHost> {
+    content
+        .lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#') && !line.starts_with('@'))
+        .filter_map(|line| line.split_whitespace().next())
+        .flat_map(|names| names.split(','))
+        .filter(|name| !name.starts_with('|') && !name.contains(['*', '?', '!']))
+        .filter_map(|name| match name.strip_prefix('[') {
+            // Non-default port: `[host]:port`
+            Some(rest) => {
+                let (host, port) = rest.split_once("]:")?;
+                Some(SshHost {
+                    host: host.to_string(),
+                    port: Some(port.parse().ok()?),
+                })
+            }
+            None => Some(SshHost {
+                host: name.to_string(),
+                port: None,
+            }),
+        })
+        .collect()
+}
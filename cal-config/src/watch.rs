@@ -0,0 +1,170 @@
+//! Watching of the directories the entries and config are loaded from (daemon only)
+
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver},
+    time::Duration,
+};
+
+use notify::{EventKind, RecommendedWatcher, Watcher};
+
+use crate::{
+    cli,
+    exec::path_dirs,
+    freedesktop::{data_dirs, desktop_file_id},
+    icons::base_dirs,
+    ssh::ssh_dir,
+};
+
+pub use notify::{RecursiveMode, Result};
+
+/// Delay without changes after which the changes are considered done
+/// (e.g. a package installation touches many files at once)
+const SETTLE: Duration = Duration::from_millis(500);
+
+/// A watch over a set of directories
+pub struct Watch {
+    /// The watcher must be kept alive to keep receiving events
+    _watcher: RecommendedWatcher,
+    receiver: Receiver<Vec<PathBuf>>,
+}
+
+impl Watch {
+    /// Watch the given directories (those that do not exist are ignored)
+    pub fn new(dirs: &[(PathBuf, RecursiveMode)]) -> Result<Self> {
+        let (sender, receiver) = mpsc::channel();
+
+        let mut watcher = notify::recommended_watcher(move |event: Result<notify::Event>| {
+            // Reading the files does not change them
+            if let Ok(event) = event
+                && !matches!(event.kind, EventKind::Access(_))
+            {
+                let _ = sender.send(event.paths);
+            }
+        })?;
+
+        for (dir, mode) in dirs.iter().filter(|(dir, _)| dir.is_dir()) {
+            watcher.watch(dir, *mode)?;
+        }
+
+        Ok(Self {
+            _watcher: watcher,
+            receiver,
+        })
+    }
+
+    /// Block until a change happens and the changes are settled, and return the changed paths.
+    /// Returns None if the watcher stopped.
+    pub fn wait(&self) -> Option<Vec<PathBuf>> {
+        let mut paths: HashSet<PathBuf> = self.receiver.recv().ok()?.into_iter().collect();
+        while let Ok(changed) = self.receiver.recv_timeout(SETTLE) {
+            paths.extend(changed);
+        }
+        Some(paths.into_iter().collect())
+    }
+}
+
+/// What changed paths affect, to only reload what is needed
+#[derive(Debug, Default)]
+pub struct Changes {
+    /// A config file changed
+    pub config: bool,
+    /// Every desktop entry must be reloaded (icon themes or directories changed)
+    pub desktop: bool,
+    /// Desktop file IDs whose files changed
+    pub desktop_ids: HashSet<String>,
+    /// Executables of `$PATH` changed (run source)
+    pub executables: bool,
+    /// SSH config or known hosts changed (ssh source)
+    pub ssh: bool,
+}
+
+impl Changes {
+    /// Sort changed paths by the source they belong to
+    pub fn new(paths: &[PathBuf]) -> Self {
+        let config_dirs = cli::config_dirs();
+        let executable_dirs = path_dirs();
+        let icon_dirs = base_dirs();
+        let ssh_dir = ssh_dir();
+        let applications_dirs: Vec<PathBuf> = data_dirs()
+            .iter()
+            .map(|dir| dir.join("applications"))
+            .collect();
+        let in_dirs = |path: &Path, dirs: &[PathBuf]| {
+            path.parent()
+                .is_some_and(|parent| dirs.iter().any(|dir| dir == parent))
+        };
+
+        let mut changes = Changes::default();
+        for path in paths {
+            if in_dirs(path, &config_dirs) {
+                changes.config = true;
+            }
+            if in_dirs(path, &executable_dirs) {
+                changes.executables = true;
+            }
+            if in_dirs(path, &icon_dirs) {
+                changes.desktop = true;
+            }
+            if ssh_dir.as_ref().is_some_and(|dir| path.starts_with(dir)) {
+                changes.ssh = true;
+            }
+
+            let applications_dir = match applications_dirs.iter().find(|dir| path.starts_with(dir))
+            {
+                Some(dir) => dir,
+                None => continue,
+            };
+            match path.extension() {
+                Some(ext) if ext == "desktop" => {
+                    changes
+                        .desktop_ids
+                        .extend(desktop_file_id(applications_dir, path));
+                }
+                // A directory of desktop files was added, moved or removed
+                None => changes.desktop = true,
+                _ => {}
+            }
+        }
+
+        changes
+    }
+}
+
+/// Directories the Freedesktop entries are loaded from: `applications` directories (recursively)
+/// and icon theme directories (new themes only, icons usually come with a new desktop file)
+pub fn freedesktop_dirs() -> Vec<(PathBuf, RecursiveMode)> {
+    let applications = data_dirs()
+        .into_iter()
+        .map(|dir| (dir.join("applications"), RecursiveMode::Recursive));
+    let icons = base_dirs()
+        .into_iter()
+        .map(|dir| (dir, RecursiveMode::NonRecursive));
+
+    applications.chain(icons).collect()
+}
+
+/// Directories the config files are loaded from (config and colors files)
+pub fn config_dirs() -> Vec<(PathBuf, RecursiveMode)> {
+    cli::config_dirs()
+        .into_iter()
+        .map(|dir| (dir, RecursiveMode::NonRecursive))
+        .collect()
+}
+
+/// Directories of `$PATH` (executables are listed by the run source)
+pub fn executable_dirs() -> Vec<(PathBuf, RecursiveMode)> {
+    path_dirs()
+        .into_iter()
+        .map(|dir| (dir, RecursiveMode::NonRecursive))
+        .collect()
+}
+
+/// User SSH directory (config, included files and known hosts)
+pub fn ssh_dirs() -> Vec<(PathBuf, RecursiveMode)> {
+    ssh_dir()
+        .map(|dir| (dir, RecursiveMode::Recursive))
+        .into_iter()
+        .collect()
+}
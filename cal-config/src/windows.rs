@@ -0,0 +1,110 @@
+//! Open windows, listed and focused through the window manager (window source)
+
+mod hyprland;
+mod sway;
+mod x11;
+
+use std::{collections::HashMap, io};
+
+use crate::Entry;
+
+pub use hyprland::Hyprland;
+pub use sway::Sway;
+pub use x11::X11;
+
+/// An open window
+#[derive(Debug, Clone, PartialEq)]
+pub struct Window {
+    /// Identifier of the window for its backend (used to focus it)
+    pub id: String,
+    /// Window title
+    pub title: String,
+    /// Wayland app id, or X11 WM class
+    pub app_id: Option<String>,
+    /// Name of the workspace the window is on
+    pub workspace: Option<String>,
+}
+
+/// A window manager, able to list and focus the open windows
+pub trait WindowBackend {
+    /// List the open windows
+    fn windows(&self) -> io::Result<Vec<Window>>;
+
+    /// Focus a window by its identifier
+    fn focus(&self, id: &str) -> io::Result<()>;
+}
+
+/// Detect the window manager from the environment:
+/// Sway / i3 (`$SWAYSOCK`, `$I3SOCK`), Hyprland (`$HYPRLAND_INSTANCE_SIGNATURE`), then X11 (`$DISPLAY`)
+pub fn detect() -> Option<Box<dyn WindowBackend>> {
+    if let Some(sway) = Sway::from_env() {
+        return Some(Box::new(sway));
+    }
+    if let Some(hyprland) = Hyprland::from_env() {
+        return Some(Box::new(hyprland));
+    }
+
+    match X11::connect() {
+        Ok(x11) => Some(Box::new(x11)),
+        Err(err) => {
+            eprintln!("No supported window manager found: {err}");
+            None
+        }
+    }
+}
+
+/// Build the entries of windows, with the icons of their applications.
+/// Applications are matched by `StartupWMClass`, then by desktop file ID (case insensitive).
+pub fn window_entries(windows: Vec<Window>, applications: &[Entry]) -> Vec<Entry> {
+    let mut icons: HashMap<String, &str> = HashMap::new();
+    for application in applications {
+        let icon = match &application.icon {
+            Some(icon) => icon.as_str(),
+            None => continue,
+        };
+        if let Some(id) = application
+            .id
+            .as_ref()
+            .and_then(|id| id.strip_suffix(".desktop"))
+        {
+            icons.entry(id.to_lowercase()).or_insert(icon);
+        }
+    }
+    // WM classes take precedence over desktop file IDs
+    for application in applications {
+        if let (Some(class), Some(icon)) = (&application.startup_wm_class, &application.icon) {
+            icons.insert(class.to_lowercase(), icon);
+        }
+    }
+
+    windows
+        .into_iter()
+        .map(|window| {
+            let icon = window
+                .app_id
+                .as_ref()
+                .and_then(|app_id| icons.get(&app_id.to_lowercase()))
+                .map(|icon| icon.to_string());
+
+            let description = match (&window.app_id, &window.workspace) {
+                (Some(app_id), Some(workspace)) => Some(format!("{app_id} ({workspace})")),
+                (Some(app_id), None) => Some(app_id.clone()),
+                (None, workspace) => workspace.clone(),
+            };
+
+            Entry {
+                // Titles are not unique (e.g. several terminals)
+                id: Some(format!("window:{}", window.id)),
+                name: match window.title.is_empty() {
+                    true => window.app_id.clone().unwrap_or_default(),
+                    false => window.title,
+                },
+                icon,
+                description,
+                keywords: window.app_id.map(|app_id| vec![app_id]),
+                window: Some(window.id),
+                ..Default::default()
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,101 @@
+//! Hyprland backend, through its request socket
+//! https://wiki.hyprland.org/IPC/
+
+use std::{
+    env,
+    io::{self, Read, Write},
+    net::Shutdown,
+    os::unix::net::UnixStream,
+    path::PathBuf,
+};
+
+use serde_json::Value;
+
+use super::{Window, WindowBackend};
+
+/// Hyprland, through its request socket (`.socket.sock`)
+pub struct Hyprland {
+    socket: PathBuf,
+}
+
+impl Hyprland {
+    /// Connect to the request socket at the given path
+    pub fn new(socket: PathBuf) -> Self {
+        Self { socket }
+    }
+
+    /// Connect to the socket of the running instance (`$HYPRLAND_INSTANCE_SIGNATURE`).
+    /// It is in `$XDG_RUNTIME_DIR/hypr`, or in `/tmp/hypr` for older versions.
+    pub fn from_env() -> Option<Self> {
+        let signature = env::var("HYPRLAND_INSTANCE_SIGNATURE")
+            .ok()
+            .filter(|signature| !signature.is_empty())?;
+
+        let sockets: Vec<PathBuf> = env::var_os("XDG_RUNTIME_DIR")
+            .map(|dir| PathBuf::from(dir).join("hypr"))
+            .into_iter()
+            .chain([PathBuf::from("/tmp/hypr")])
+            .map(|dir| dir.join(&signature).join(".socket.sock"))
+            .collect();
+
+        let socket = sockets
+            .iter()
+            .find(|socket| socket.exists())
+            .unwrap_or(&sockets[0]);
+
+        Some(Self::new(socket.clone()))
+    }
+
+    /// Send a request and read its reply (one connection per request)
+    fn request(&self, request: &str) -> io::Result<String> {
+        let mut stream = UnixStream::connect(&self.socket)?;
+        stream.write_all(request.as_bytes())?;
+        stream.shutdown(Shutdown::Write)?;
+
+        let mut reply = String::new();
+        stream.read_to_string(&mut reply)?;
+        Ok(reply)
+    }
+}
+
+impl WindowBackend for Hyprland {
+    fn windows(&self) -> io::Result<Vec<Window>> {
+        let clients: Vec<Value> = serde_json::from_str(&self.request("j/clients")?)?;
+
+        let windows = clients
+            .iter()
+            .filter(|client| {
+                client.get("mapped").and_then(|mapped| mapped.as_bool()) != Some(false)
+                    && client.get("hidden").and_then(|hidden| hidden.as_bool()) != Some(true)
+            })
+            .filter_map(|client| {
+                let string = |key: &str| client.get(key).and_then(|value| value.as_str());
+
+                Some(Window {
+                    id: string("address")?.to_string(),
+                    title: string("title").unwrap_or_default().to_string(),
+                    app_id: string("class")
+                        .filter(|class| !class.is_empty())
+                        .map(|class| class.to_string()),
+                    workspace: client
+                        .get("workspace")
+                        .and_then(|workspace| workspace.get("name"))
+                        .and_then(|name| name.as_str())
+                        .map(|name| name.to_string()),
+                })
+            })
+            .collect();
+
+        Ok(windows)
+    }
+
+    fn focus(&self, id: &str) -> io::Result<()> {
+        match self
+            .request(&format!("dispatch focuswindow address:{id}"))?
+            .trim()
+        {
+            "ok" => Ok(()),
+            error => Err(io::Error::other(error.to_string())),
+        }
+    }
+}
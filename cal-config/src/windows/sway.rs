@@ -0,0 +1,133 @@
+//! Sway / i3 IPC backend
+//! https://man.archlinux.org/man/sway-ipc.7
+
+use std::{
+    env,
+    io::{self, Read, Write},
+    os::unix::net::UnixStream,
+    path::PathBuf,
+};
+
+use serde_json::Value;
+
+use super::{Window, WindowBackend};
+
+/// Magic string starting every message
+const MAGIC: &[u8] = b"i3-ipc";
+
+/// Message type to run a command
+const RUN_COMMAND: u32 = 0;
+
+/// Message type to get the layout tree
+const GET_TREE: u32 = 4;
+
+/// Sway or i3, through their IPC socket
+pub struct Sway {
+    socket: PathBuf,
+}
+
+impl Sway {
+    /// Connect to the IPC socket at the given path
+    pub fn new(socket: PathBuf) -> Self {
+        Self { socket }
+    }
+
+    /// Connect to the socket of the running Sway (`$SWAYSOCK`) or i3 (`$I3SOCK`) instance
+    pub fn from_env() -> Option<Self> {
+        env::var_os("SWAYSOCK")
+            .or_else(|| env::var_os("I3SOCK"))
+            .filter(|socket| !socket.is_empty())
+            .map(|socket| Self::new(socket.into()))
+    }
+
+    /// Send a message and read its reply
+    fn request(&self, message_type: u32, payload: &str) -> io::Result<Value> {
+        let mut stream = UnixStream::connect(&self.socket)?;
+
+        let mut message = Vec::with_capacity(MAGIC.len() + 8 + payload.len());
+        message.extend_from_slice(MAGIC);
+        message.extend_from_slice(&(payload.len() as u32).to_ne_bytes());
+        message.extend_from_slice(&message_type.to_ne_bytes());
+        message.extend_from_slice(payload.as_bytes());
+        stream.write_all(&message)?;
+
+        let mut header = [0; 14];
+        stream.read_exact(&mut header)?;
+        if &header[..MAGIC.len()] != MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "invalid IPC reply",
+            ));
+        }
+        let length = u32::from_ne_bytes([header[6], header[7], header[8], header[9]]);
+
+        let mut reply = vec![0; length as usize];
+        stream.read_exact(&mut reply)?;
+
+        Ok(serde_json::from_slice(&reply)?)
+    }
+}
+
+impl WindowBackend for Sway {
+    fn windows(&self) -> io::Result<Vec<Window>> {
+        let tree = self.request(GET_TREE, "")?;
+
+        let mut windows = Vec::new();
+        collect_windows(&tree, None, &mut windows);
+        Ok(windows)
+    }
+
+    fn focus(&self, id: &str) -> io::Result<()> {
+        let reply = self.request(RUN_COMMAND, &format!("[con_id={id}] focus"))?;
+
+        // One result per command
+        match reply.get(0).and_then(|result| result.get("success")) {
+            Some(Value::Bool(true)) => Ok(()),
+            _ => Err(io::Error::other(
+                reply
+                    .get(0)
+                    .and_then(|result| result.get("error"))
+                    .and_then(|error| error.as_str())
+                    .unwrap_or("failed to focus the window")
+                    .to_string(),
+            )),
+        }
+    }
+}
+
+/// Collect the windows of a layout tree node, recursively
+fn collect_windows(node: &Value, workspace: Option<&str>, windows: &mut Vec<Window>) {
+    let string = |key: &str| node.get(key).and_then(|value| value.as_str());
+
+    let workspace = match string("type") {
+        Some("workspace") => string("name"),
+        _ => workspace,
+    };
+
+    // Wayland windows have an app id, X11 ones (i3, XWayland) a window id
+    let is_window = ["app_id", "window"]
+        .iter()
+        .any(|key| node.get(key).is_some_and(|value| !value.is_null()));
+
+    if is_window && let Some(id) = node.get("id").and_then(|id| id.as_u64()) {
+        let class = node
+            .get("window_properties")
+            .and_then(|properties| properties.get("class"))
+            .and_then(|class| class.as_str());
+
+        windows.push(Window {
+            id: id.to_string(),
+            title: string("name").unwrap_or_default().to_string(),
+            app_id: string("app_id").or(class).map(|s| s.to_string()),
+            workspace: workspace.map(|s| s.to_string()),
+        });
+    }
+
+    for key in ["nodes", "floating_nodes"] {
+        if let Some(children) = node.get(key).and_then(|nodes| nodes.as_array()) {
+            for child in children {
+                collect_windows(child, workspace, windows);
+            }
+        }
+    }
+}
@@ -0,0 +1,141 @@
+//! X11 backend, following the Extended Window Manager Hints
+//! https://specifications.freedesktop.org/wm-spec/latest/
+
+use std::io;
+
+use x11rb::{
+    connection::Connection,
+    protocol::xproto::{
+        AtomEnum, ClientMessageEvent, ConnectionExt as _, EventMask, Window as XWindow,
+    },
+    rust_connection::RustConnection,
+};
+
+use super::{Window, WindowBackend};
+
+x11rb::atom_manager! {
+    /// Atoms used by the backend
+    Atoms: AtomsCookie {
+        _NET_CLIENT_LIST,
+        _NET_ACTIVE_WINDOW,
+        _NET_WM_NAME,
+        UTF8_STRING,
+    }
+}
+
+/// An EWMH compliant X11 window manager
+pub struct X11 {
+    connection: RustConnection,
+    root: XWindow,
+    atoms: Atoms,
+}
+
+impl X11 {
+    /// Connect to the X server of `$DISPLAY`
+    pub fn connect() -> io::Result<Self> {
+        let (connection, screen) = x11rb::connect(None).map_err(io::Error::other)?;
+        let root = connection.setup().roots[screen].root;
+        let atoms = Atoms::new(&connection)
+            .map_err(io::Error::other)?
+            .reply()
+            .map_err(io::Error::other)?;
+
+        Ok(Self {
+            connection,
+            root,
+            atoms,
+        })
+    }
+
+    /// Read a property of a window, as bytes
+    fn property(
+        &self,
+        window: XWindow,
+        property: impl Into<u32>,
+        kind: impl Into<u32>,
+    ) -> io::Result<Vec<u8>> {
+        let reply = self
+            .connection
+            .get_property(false, window, property, kind, 0, u32::MAX)
+            .map_err(io::Error::other)?
+            .reply()
+            .map_err(io::Error::other)?;
+
+        Ok(reply.value)
+    }
+}
+
+impl WindowBackend for X11 {
+    fn windows(&self) -> io::Result<Vec<Window>> {
+        let clients = self
+            .connection
+            .get_property(
+                false,
+                self.root,
+                self.atoms._NET_CLIENT_LIST,
+                AtomEnum::WINDOW,
+                0,
+                u32::MAX,
+            )
+            .map_err(io::Error::other)?
+            .reply()
+            .map_err(io::Error::other)?;
+
+        let windows = clients
+            .value32()
+            .into_iter()
+            .flatten()
+            .map(|window| {
+                // _NET_WM_NAME is UTF-8, WM_NAME is the legacy fallback
+                let mut title =
+                    self.property(window, self.atoms._NET_WM_NAME, self.atoms.UTF8_STRING)?;
+                if title.is_empty() {
+                    title = self.property(window, AtomEnum::WM_NAME, AtomEnum::STRING)?;
+                }
+
+                // WM_CLASS contains the instance and class names, null terminated
+                let class = self.property(window, AtomEnum::WM_CLASS, AtomEnum::STRING)?;
+                let class = class
+                    .split(|byte| *byte == 0)
+                    .nth(1)
+                    .filter(|class| !class.is_empty())
+                    .map(|class| String::from_utf8_lossy(class).to_string());
+
+                Ok(Window {
+                    id: window.to_string(),
+                    title: String::from_utf8_lossy(&title).to_string(),
+                    app_id: class,
+                    workspace: None,
+                })
+            })
+            .collect::<io::Result<Vec<Window>>>()?;
+
+        Ok(windows)
+    }
+
+    fn focus(&self, id: &str) -> io::Result<()> {
+        let window: XWindow = id
+            .parse()
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "invalid X11 window id"))?;
+
+        // Source indication 2: the request comes from a pager-like tool
+        let event = ClientMessageEvent::new(
+            32,
+            window,
+            self.atoms._NET_ACTIVE_WINDOW,
+            [2, x11rb::CURRENT_TIME, 0, 0, 0],
+        );
+
+        self.connection
+            .send_event(
+                false,
+                self.root,
+                EventMask::SUBSTRUCTURE_REDIRECT | EventMask::SUBSTRUCTURE_NOTIFY,
+                event,
+            )
+            .map_err(io::Error::other)?;
+        self.connection.flush().map_err(io::Error::other)?;
+
+        Ok(())
+    }
+}
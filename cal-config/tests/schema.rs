@@ -0,0 +1,13 @@
+//! The committed `schema.json` must match the config types
+
+use cal_config::schema::schema;
+
+#[test]
+fn schema_is_up_to_date() {
+    let committed = include_str!("../../schema.json");
+
+    assert!(
+        committed == schema(),
+        "schema.json is out of date, regenerate it with `cal --print-schema > schema.json`"
+    );
+}
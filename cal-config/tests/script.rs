@@ -0,0 +1,65 @@
+//! Rofi-style scripts
+
+use std::{env, fs, os::unix::fs::PermissionsExt, path::PathBuf};
+
+use cal_config::{
+    Entry,
+    script::{parse_script_output, run_script},
+};
+
+/// Write an executable script, unique to the test
+fn script_path(name: &str, content: &str) -> PathBuf {
+    let path = env::temp_dir().join(format!("cal-test-{}-{name}.sh", std::process::id()));
+    fs::write(&path, content).unwrap();
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+    path
+}
+
+#[test]
+fn parses_row_options() {
+    let output = "\0prompt\x1fPick\n\
+                  plain\n\
+                  \n\
+                  with icon\0icon\x1ffirefox\x1fmeta\x1fweb browser\x1finfo\x1fdata\n\
+                  header\0nonselectable\x1ftrue\n";
+
+    let entries = parse_script_output(output);
+
+    let names: Vec<&str> = entries.iter().map(|entry| entry.name.as_str()).collect();
+    assert_eq!(names, vec!["plain", "with icon"]);
+    assert_eq!(entries[0].id.as_deref(), Some("line:2"));
+    assert_eq!(entries[1].id.as_deref(), Some("line:4"));
+    assert_eq!(entries[0].icon, None);
+    assert_eq!(entries[1].icon.as_deref(), Some("firefox"));
+    assert_eq!(
+        entries[1].keywords,
+        Some(vec!["web".to_string(), "browser".to_string()])
+    );
+    assert_eq!(entries[1].info.as_deref(), Some("data"));
+}
+
+#[test]
+fn reruns_with_the_selection() {
+    let path = script_path(
+        "rerun",
+        "#!/bin/sh\n\
+         if [ \"$ROFI_RETV\" = 0 ]; then printf 'first\\0info\\037one\\n'; exit; fi\n\
+         if [ \"$1\" = first ]; then printf '%s-%s\\n' \"$1\" \"$ROFI_INFO\"; fi\n",
+    );
+
+    let initial = run_script(&path, None).unwrap();
+    let next = run_script(&path, Some(&initial[0])).unwrap();
+    let last = run_script(
+        &path,
+        Some(&Entry {
+            name: "first-one".to_string(),
+            ..Default::default()
+        }),
+    )
+    .unwrap();
+
+    assert_eq!(initial[0].name, "first");
+    assert_eq!(next[0].name, "first-one");
+    assert!(last.is_empty());
+    let _ = fs::remove_file(path);
+}
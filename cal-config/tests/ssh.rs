@@ -0,0 +1,84 @@
+//! SSH hosts, parsed from fixture files
+
+use std::path::{Path, PathBuf};
+
+use cal_config::ssh::{SshHost, config_hosts, hosts_from_dir, known_hosts_hosts};
+
+/// Fixture SSH directory
+fn ssh_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/ssh")
+}
+
+/// Host on the default port
+fn host(name: &str) -> SshHost {
+    SshHost {
+        host: name.to_string(),
+        port: None,
+    }
+}
+
+#[test]
+fn config_hosts_follow_includes_and_skip_patterns() {
+    let dir = ssh_dir();
+
+    assert_eq!(
+        config_hosts(&dir.join("config"), &dir),
+        vec![
+            host("github.com"),
+            host("home"),
+            host("nas"),
+            host("bastion"),
+            host("build-server"),
+            host("lab"),
+            host("quoted host"),
+        ]
+    );
+}
+
+#[test]
+fn known_hosts_skip_hashed_names_and_patterns() {
+    let content = std::fs::read_to_string(ssh_dir().join("known_hosts")).unwrap();
+
+    assert_eq!(
+        known_hosts_hosts(&content),
+        vec![
+            host("github.com"),
+            host("140.82.121.4"),
+            SshHost {
+                host: "git.example.com".to_string(),
+                port: Some(2222),
+            },
+            host("newhost"),
+        ]
+    );
+}
+
+#[test]
+fn hosts_are_deduplicated_and_run_ssh_in_a_terminal() {
+    let hosts = hosts_from_dir(&ssh_dir());
+    let names: Vec<&str> = hosts.iter().map(|host| host.host.as_str()).collect();
+
+    assert_eq!(
+        names,
+        vec![
+            "github.com",
+            "home",
+            "nas",
+            "bastion",
+            "build-server",
+            "lab",
+            "quoted host",
+            "140.82.121.4",
+            "git.example.com",
+            "newhost",
+        ]
+    );
+
+    let entry = hosts[6].entry();
+    assert_eq!(entry.command, "ssh \"quoted host\"");
+    assert!(entry.terminal);
+
+    let entry = hosts[8].entry();
+    assert_eq!(entry.name, "git.example.com:2222");
+    assert_eq!(entry.command, "ssh -p 2222 git.example.com");
+}
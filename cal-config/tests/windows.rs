@@ -0,0 +1,157 @@
+//! Window backends, against fake IPC sockets
+
+use std::{
+    env,
+    io::{Read, Write},
+    os::unix::net::UnixListener,
+    path::PathBuf,
+    thread,
+};
+
+use cal_config::windows::{Hyprland, Sway, Window, WindowBackend, window_entries};
+
+/// Path of a fake socket, unique to the test
+fn socket_path(name: &str) -> PathBuf {
+    let path = env::temp_dir().join(format!("cal-test-{}-{name}.sock", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+/// Serve Sway IPC replies, one connection per reply. Returns the received payloads.
+fn fake_sway(
+    listener: UnixListener,
+    replies: Vec<&'static str>,
+) -> thread::JoinHandle<Vec<String>> {
+    thread::spawn(move || {
+        replies
+            .into_iter()
+            .map(|reply| {
+                let (mut stream, _) = listener.accept().unwrap();
+
+                let mut header = [0; 14];
+                stream.read_exact(&mut header).unwrap();
+                assert_eq!(&header[..6], b"i3-ipc");
+                let length = u32::from_ne_bytes(header[6..10].try_into().unwrap());
+                let mut payload = vec![0; length as usize];
+                stream.read_exact(&mut payload).unwrap();
+
+                let mut message = b"i3-ipc".to_vec();
+                message.extend_from_slice(&(reply.len() as u32).to_ne_bytes());
+                message.extend_from_slice(&header[10..14]);
+                message.extend_from_slice(reply.as_bytes());
+                stream.write_all(&message).unwrap();
+
+                String::from_utf8(payload).unwrap()
+            })
+            .collect()
+    })
+}
+
+/// Serve Hyprland replies, one connection per reply. Returns the received requests.
+fn fake_hyprland(
+    listener: UnixListener,
+    replies: Vec<&'static str>,
+) -> thread::JoinHandle<Vec<String>> {
+    thread::spawn(move || {
+        replies
+            .into_iter()
+            .map(|reply| {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut request = String::new();
+                stream.read_to_string(&mut request).unwrap();
+                stream.write_all(reply.as_bytes()).unwrap();
+                request
+            })
+            .collect()
+    })
+}
+
+#[test]
+fn sway_lists_and_focuses_windows() {
+    let path = socket_path("sway");
+    let listener = UnixListener::bind(&path).unwrap();
+
+    let tree = r#"{"id": 1, "type": "root", "nodes": [
+        {"id": 2, "type": "output", "nodes": [
+            {"id": 3, "type": "workspace", "name": "1", "nodes": [
+                {"id": 4, "type": "con", "name": "Terminal", "app_id": "foot", "nodes": []}
+            ], "floating_nodes": [
+                {"id": 5, "type": "floating_con", "name": "Firefox", "app_id": null, "window": 42,
+                 "window_properties": {"class": "firefox"}, "nodes": []}
+            ]}
+        ]}
+    ]}"#;
+    let server = fake_sway(listener, vec![tree, r#"[{"success": true}]"#]);
+
+    let sway = Sway::new(path.clone());
+    let windows = sway.windows().unwrap();
+    sway.focus("5").unwrap();
+
+    assert_eq!(
+        windows,
+        vec![
+            Window {
+                id: "4".into(),
+                title: "Terminal".into(),
+                app_id: Some("foot".into()),
+                workspace: Some("1".into()),
+            },
+            Window {
+                id: "5".into(),
+                title: "Firefox".into(),
+                app_id: Some("firefox".into()),
+                workspace: Some("1".into()),
+            },
+        ]
+    );
+    assert_eq!(server.join().unwrap(), vec!["", "[con_id=5] focus"]);
+    let _ = std::fs::remove_file(path);
+}
+
+#[test]
+fn hyprland_lists_and_focuses_windows() {
+    let path = socket_path("hyprland");
+    let listener = UnixListener::bind(&path).unwrap();
+
+    let clients = r#"[
+        {"address": "0x1", "mapped": true, "hidden": false, "title": "Terminal", "class": "kitty",
+         "workspace": {"id": 2, "name": "2"}},
+        {"address": "0x2", "mapped": false, "hidden": false, "title": "Unmapped", "class": "x",
+         "workspace": {"id": 2, "name": "2"}}
+    ]"#;
+    let server = fake_hyprland(listener, vec![clients, "ok"]);
+
+    let hyprland = Hyprland::new(path.clone());
+    let windows = hyprland.windows().unwrap();
+    hyprland.focus("0x1").unwrap();
+
+    assert_eq!(
+        windows,
+        vec![Window {
+            id: "0x1".into(),
+            title: "Terminal".into(),
+            app_id: Some("kitty".into()),
+            workspace: Some("2".into()),
+        }]
+    );
+    assert_eq!(
+        server.join().unwrap(),
+        vec!["j/clients", "dispatch focuswindow address:0x1"]
+    );
+    let _ = std::fs::remove_file(path);
+}
+
+#[test]
+fn window_entries_are_unique() {
+    let terminal = |id: &str| Window {
+        id: id.into(),
+        title: "~".into(),
+        app_id: Some("foot".into()),
+        workspace: None,
+    };
+
+    let entries = window_entries(vec![terminal("4"), terminal("7")], &[]);
+
+    let ids: Vec<_> = entries.iter().map(|entry| entry.id.as_deref()).collect();
+    assert_eq!(ids, vec![Some("window:4"), Some("window:7")]);
+}
@@ -1,9 +1,31 @@
+use cal_config::{Config, Entry};
+use serde::{Deserialize, Serialize};
 use std::io;
 use tokio::io::AsyncReadExt;
 use tokio::io::AsyncWriteExt;
 
 pub const SOCKET: &str = "/tmp/cal-daemon.sock";
 
+/// Request sent by a client to the daemon
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Request {
+    /// Show the launcher window with the given config
+    Show { config: Config },
+}
+
+/// Response sent back to the client once the launcher window is resolved
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Response {
+    /// The user selected entries (`input` is the text typed in the search bar)
+    Selected { entries: Vec<Entry>, input: String },
+    /// The window was closed without selecting anything
+    Cancelled,
+    /// The request could not be processed
+    Error { message: String },
+}
+
 /// Write a message to a socket (via JSON serialization)
 pub fn send_message<T, W>(message: &T, writable: &mut W) -> io::Result<()>
 where
@@ -28,8 +50,8 @@ where
     let bytes = serde_json::to_vec(message).expect("Failed to serialize message");
     let length = bytes.len() as u32;
 
-    writable.write(&length.to_be_bytes()).await?;
-    writable.write(&bytes).await?;
+    writable.write_all(&length.to_be_bytes()).await?;
+    writable.write_all(&bytes).await?;
 
     Ok(())
 }
@@ -48,7 +70,8 @@ where
     let mut bytes = vec![0u8; length];
     readable.read_exact(&mut bytes)?;
 
-    let message: T = serde_json::from_slice(&bytes).expect("Failed to deserialize message");
+    let message: T = serde_json::from_slice(&bytes)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
 
     Ok(message)
 }
@@ -60,14 +83,15 @@ where
     R: tokio::io::AsyncRead + Unpin,
 {
     let mut length_bytes = [0u8; 4];
-    readable.read(&mut length_bytes).await?;
+    readable.read_exact(&mut length_bytes).await?;
 
     let length = u32::from_be_bytes(length_bytes) as usize;
 
     let mut bytes = vec![0u8; length];
-    readable.read(&mut bytes).await?;
+    readable.read_exact(&mut bytes).await?;
 
-    let message: T = serde_json::from_slice(&bytes).expect("Failed to deserialize message");
+    let message: T = serde_json::from_slice(&bytes)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
 
     Ok(message)
 }
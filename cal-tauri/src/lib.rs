@@ -1,14 +1,33 @@
 use std::{
+    collections::HashMap,
     env,
     fs::{self, read_to_string},
     process::Stdio,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, RwLock,
+    },
 };
 
-use cal_config::{cli::directories, Config, Entry};
-use cal_daemon::read_message_async;
+use cal_config::{
+    cli::{directories, load_cli_config},
+    config::EntrySource,
+    entries::{
+        desktop_entries, from_cached_source, from_combi, from_freedesktop, from_script,
+        from_source, from_sources, from_windows, update_freedesktop,
+    },
+    exec::parse_command,
+    watch::{config_dirs, executable_dirs, freedesktop_dirs, ssh_dirs, Changes, Watch},
+    windows::detect,
+    Config, Entry,
+};
+use cal_daemon::{read_message_async, send_message_async, Request, Response};
 use serde_json::{Map, Value};
 use tauri::{ipc::Channel, Manager, RunEvent, State, WindowEvent};
-use tokio::{net::UnixListener, sync::Mutex};
+use tokio::{
+    net::UnixListener,
+    sync::{oneshot, Mutex},
+};
 
 /// Path to the counts file
 const COUNTS: &str = "counts.json";
@@ -41,9 +60,156 @@ fn update_counts(counts: Map<String, Value>) {
 
 pub enum AppState {
     /// One run only: contains the full config to send to the frontend
-    Once(Config),
-    /// Daemon mode: contains the cached desktop entries for default usage of the launcher
-    Daemon(UnixListener, Vec<Entry>),
+    Once(Box<Config>),
+    /// Daemon mode: contains the cached entries of each source for default usage of the launcher
+    Daemon(UnixListener, Arc<RwLock<SourceEntries>>),
+}
+
+/// Cached entries, by source, with the config they were loaded with
+pub struct SourceEntries {
+    pub config: Config,
+    pub entries: HashMap<EntrySource, Vec<Entry>>,
+}
+
+impl SourceEntries {
+    /// Load the entries of every cached source
+    pub fn new(config: Config) -> Self {
+        Self {
+            entries: from_sources(&config),
+            config,
+        }
+    }
+
+    /// Cached entries of a source
+    fn get(&self, source: EntrySource) -> &[Entry] {
+        self.entries
+            .get(&source)
+            .map(|entries| entries.as_slice())
+            .unwrap_or_default()
+    }
+}
+
+/// Reload the config and the cached entries whenever their files change (daemon only),
+/// so that the next window uses them. Only the changed sources are loaded again,
+/// and only the changed desktop files are parsed again. This blocks the current thread.
+pub fn watch_entries(cache: Arc<RwLock<SourceEntries>>) {
+    let dirs = [
+        freedesktop_dirs(),
+        executable_dirs(),
+        ssh_dirs(),
+        config_dirs(),
+    ]
+    .concat();
+    let watch = match Watch::new(&dirs) {
+        Ok(watch) => watch,
+        Err(err) => {
+            eprintln!("Failed to watch the entries and config directories: {err}");
+            return;
+        }
+    };
+
+    while let Some(paths) = watch.wait() {
+        let changes = Changes::new(&paths);
+        let previous = cache.read().unwrap().config.clone();
+
+        // An invalid config is reported, the previous one is kept until it is fixed
+        let config = match changes.config {
+            true => load_cli_config().unwrap_or_else(|err| {
+                eprintln!("Failed to reload the config, keeping the previous one: {err}");
+                previous.clone()
+            }),
+            false => previous.clone(),
+        };
+
+        // Only the sources that changed are loaded again, outside of the lock
+        let mut reloaded = Vec::new();
+        if changes.desktop || !config.same_freedesktop(&previous) {
+            let entries = from_cached_source(&config, EntrySource::Desktop);
+            reloaded.push((EntrySource::Desktop, entries));
+        } else if !changes.desktop_ids.is_empty() {
+            let mut entries = cache.read().unwrap().get(EntrySource::Desktop).to_vec();
+            update_freedesktop(&mut entries, &changes.desktop_ids, &config);
+            reloaded.push((EntrySource::Desktop, entries));
+        }
+        if changes.executables {
+            reloaded.push((
+                EntrySource::Run,
+                from_cached_source(&config, EntrySource::Run),
+            ));
+        }
+        if changes.ssh {
+            reloaded.push((
+                EntrySource::Ssh,
+                from_cached_source(&config, EntrySource::Ssh),
+            ));
+        }
+
+        let mut cache = cache.write().unwrap();
+        cache.config = config;
+        cache.entries.extend(reloaded);
+    }
+}
+
+/// The response awaited by the client of the current window, if any.
+/// The lock is never held across an await, so that it can be taken from the event loop.
+#[derive(Default)]
+pub struct PendingResponse(std::sync::Mutex<Option<oneshot::Sender<Response>>>);
+
+impl PendingResponse {
+    /// Await a new response (the previous request, if any, is cancelled)
+    fn replace(&self) -> oneshot::Receiver<Response> {
+        let (sender, receiver) = oneshot::channel();
+        *self.0.lock().unwrap() = Some(sender);
+        receiver
+    }
+
+    /// Resolve the pending request with a response. Returns whether there was one.
+    fn resolve(&self, response: Response) -> bool {
+        let sender = self.0.lock().unwrap().take();
+        sender.map(|sender| sender.send(response)).is_some()
+    }
+
+    /// Cancel the pending request. Returns whether there was one.
+    fn cancel(&self) -> bool {
+        self.resolve(Response::Cancelled)
+    }
+}
+
+/// The config currently displayed by the frontend, and the channel it was sent through
+/// (used to replace the entries of a script while its window is open),
+/// with the number of configs displayed so far
+#[derive(Default)]
+pub struct Session(Mutex<Option<(Config, Channel<Config>)>>, AtomicUsize);
+
+impl Session {
+    /// Display a config in the frontend, replacing the current one
+    async fn show(&self, config: Config, channel: &Channel<Config>) {
+        channel.send(config.clone()).unwrap();
+        let mut current = self.0.lock().await;
+        self.1.fetch_add(1, Ordering::Relaxed);
+        *current = Some((config, channel.clone()));
+    }
+}
+
+/// Exit once the launcher window is resolved, with a non-zero code if cancelled.
+/// In dmenu mode, the selected entries (or the typed text) are printed to stdout.
+pub fn exit_with_response(response: Response, dmenu: bool) -> ! {
+    match response {
+        Response::Selected { entries, input } => {
+            if dmenu {
+                match entries.is_empty() {
+                    true => println!("{input}"),
+                    false => entries.iter().for_each(|entry| println!("{}", entry.name)),
+                }
+            }
+            std::process::exit(0)
+        }
+        Response::Cancelled => std::process::exit(1),
+        Response::Error { message } => {
+            eprintln!("Error: {message}");
+            std::process::exit(1)
+        }
+    }
 }
 
 /// Load the App Launcher entries via a channel
@@ -51,41 +217,154 @@ pub enum AppState {
 #[tauri::command]
 async fn subscribe_config(
     state: State<'_, Mutex<AppState>>,
-    channel: Channel<&Config>,
+    pending: State<'_, PendingResponse>,
+    session: State<'_, Session>,
+    channel: Channel<Config>,
 ) -> Result<(), ()> {
     match &*state.lock().await {
-        AppState::Once(config) => channel.send(config).unwrap(),
-        AppState::Daemon(listener, entries) => {
+        AppState::Once(config) => {
+            if config.dmenu {
+                let receiver = pending.replace();
+                tauri::async_runtime::spawn(async move {
+                    exit_with_response(receiver.await.unwrap_or(Response::Cancelled), true);
+                });
+            }
+            session.show(Config::clone(config), &channel).await
+        }
+        AppState::Daemon(listener, cache) => {
             println!("Daemon mode: listening for commands...");
             while let Ok((mut stream, _)) = listener.accept().await {
-                let config: Option<Config> = read_message_async(&mut stream).await.ok();
-                if let Some(mut config) = config {
-                    if config.entries.is_empty() {
-                        config.entries = entries.clone();
+                let mut config = match read_message_async(&mut stream).await {
+                    Ok(Request::Show { config }) => config,
+                    Err(err) => {
+                        let response = Response::Error {
+                            message: err.to_string(),
+                        };
+                        let _ = send_message_async(&response, &mut stream).await;
+                        continue;
                     }
-                    config.daemon = true; // Signal to the frontend that it must remain open
+                };
 
-                    channel.send(&config).unwrap();
+                if config.loads_source() && config.source == EntrySource::Script {
+                    // Scripts may take a while: they must not block the async runtime
+                    let script_config = config.clone();
+                    config.entries = tauri::async_runtime::spawn_blocking(move || {
+                        from_script(&script_config, None)
+                    })
+                    .await
+                    .unwrap_or_default();
+                } else if config.loads_source() {
+                    let cache = cache.read().unwrap();
+                    let load = |source: EntrySource| match source {
+                        // Categories and actions are applied as the request config says,
+                        // and entries translated or with icons other than the daemon ones
+                        // are loaded again
+                        EntrySource::Desktop => desktop_entries(
+                            match cache.config.same_freedesktop(&config) {
+                                true => cache.get(source).to_vec(),
+                                false => from_freedesktop(&config),
+                            },
+                            &config,
+                        ),
+                        source if source.is_cached() => cache.get(source).to_vec(),
+                        // Open windows are listed on demand, with the cached application icons
+                        EntrySource::Window => from_windows(cache.get(EntrySource::Desktop)),
+                        source => from_source(&config, source),
+                    };
+                    config.entries = match config.source {
+                        EntrySource::Combi => from_combi(&config, load),
+                        source => load(source),
+                    };
                 }
+                config.daemon = true; // Signal to the frontend that it must remain open
+
+                // Reply to the client once the window resolves
+                let receiver = pending.replace();
+                tauri::async_runtime::spawn(async move {
+                    let response = receiver.await.unwrap_or(Response::Cancelled);
+                    let _ = send_message_async(&response, &mut stream).await;
+                });
+
+                session.show(config, &channel).await;
             }
         }
     }
     Ok(())
 }
 
-/// Run a command
+/// Resolve the current request with the selected entries
 #[tauri::command]
-fn run_command(command: String, terminal: bool) {
-    let mut command = command
-        .split_whitespace()
-        .filter(|&arg| match arg {
-            "%U" | "%u" | "%F" | "%f" | "@@" | "@@u" => false,
-            _ => true,
-        })
-        .map(|arg| arg.to_string())
-        .collect::<Vec<_>>();
+async fn select_entries(
+    pending: State<'_, PendingResponse>,
+    entries: Vec<Entry>,
+    input: String,
+) -> Result<(), ()> {
+    pending.resolve(Response::Selected { entries, input });
+    Ok(())
+}
+
+/// Run the script of the current window again with the selected entry (script source),
+/// and display the entries it prints instead of the current ones.
+/// Returns false when the script printed nothing (or failed): the selection is then final.
+#[tauri::command]
+async fn select_script_entry(session: State<'_, Session>, entry: Entry) -> Result<bool, ()> {
+    let (config, shown) = match &*session.0.lock().await {
+        Some((config, _)) if config.source == EntrySource::Script => {
+            (config.clone(), session.1.load(Ordering::Relaxed))
+        }
+        _ => return Ok(false),
+    };
+
+    // The session is not locked while the script runs, so that other requests are not blocked
+    let entries = tauri::async_runtime::spawn_blocking(move || from_script(&config, Some(&entry)))
+        .await
+        .unwrap_or_default();
+    if entries.is_empty() {
+        return Ok(false);
+    }
 
-    if terminal {
+    match session.0.lock().await.as_mut() {
+        Some((config, channel)) if session.1.load(Ordering::Relaxed) == shown => {
+            config.entries = entries;
+            channel.send(config.clone()).unwrap();
+        }
+        // Another config was displayed while the script ran: the selection is dropped
+        _ => {}
+    }
+    Ok(true)
+}
+
+/// Resolve the current request as cancelled
+#[tauri::command]
+async fn cancel(pending: State<'_, PendingResponse>) -> Result<(), ()> {
+    pending.cancel();
+    Ok(())
+}
+
+/// Run the command of an entry (or open its URL, or focus its window)
+#[tauri::command]
+fn run_command(entry: Entry) {
+    if let Some(url) = &entry.url {
+        let _ = tauri_plugin_opener::open_url(url, None::<&str>);
+        return;
+    }
+
+    if let Some(window) = &entry.window {
+        if let Some(Err(err)) = detect().map(|backend| backend.focus(window)) {
+            eprintln!("Failed to focus {}: {}", entry.name, err);
+        }
+        return;
+    }
+
+    let mut command = match parse_command(&entry) {
+        Ok(command) => command,
+        Err(err) => {
+            eprintln!("Invalid command for {}: {}", entry.name, err);
+            return;
+        }
+    };
+
+    if entry.terminal {
         for arg in get_default_terminal().into_iter().rev() {
             command.insert(0, arg);
         }
@@ -127,8 +406,13 @@ pub fn run(state: AppState, daemon: bool) {
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
         .manage(Mutex::new(state))
+        .manage(PendingResponse::default())
+        .manage(Session::default())
         .invoke_handler(tauri::generate_handler![
             subscribe_config,
+            select_entries,
+            select_script_entry,
+            cancel,
             run_command,
             get_counts,
             update_counts
@@ -143,6 +427,12 @@ pub fn run(state: AppState, daemon: bool) {
                     // Remove the socket
                     let _ = std::fs::remove_file(cal_daemon::SOCKET);
                 }
+                // The window was closed without selecting anything (dmenu only)
+                (false, RunEvent::ExitRequested { .. })
+                    if a.state::<PendingResponse>().cancel() =>
+                {
+                    std::process::exit(1);
+                }
                 (
                     true,
                     RunEvent::WindowEvent {
@@ -152,6 +442,7 @@ pub fn run(state: AppState, daemon: bool) {
                     },
                 ) => {
                     api.prevent_close();
+                    a.state::<PendingResponse>().cancel();
                     a.get_webview_window(&label).unwrap().hide().unwrap();
                 }
                 _ => {}
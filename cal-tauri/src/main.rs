@@ -1,11 +1,20 @@
 // Prevents additional console window on Windows in release, DO NOT REMOVE!!
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-use std::os::unix::net::UnixStream;
+use std::{
+    io,
+    os::unix::net::UnixStream,
+    sync::{Arc, RwLock},
+    thread,
+};
 
-use cal_config::{cli::process_cli_config, entries::from_freedesktop};
-use cal_daemon::{send_message, SOCKET};
-use launcher_lib::AppState;
+use cal_config::{
+    cli::{process_cli_config, CliAction},
+    entries::{from_csv, from_lines, from_source},
+    Diagnostic, Level,
+};
+use cal_daemon::{read_message, send_message, Request, Response, SOCKET};
+use launcher_lib::{exit_with_response, watch_entries, AppState, SourceEntries};
 use tokio::net::UnixListener;
 
 /// Close the socket when interrupted (daemon only)
@@ -19,23 +28,75 @@ async fn wait_for_termination() {
     std::process::exit(0);
 }
 
+/// Print the problems found by `cal check`.
+/// Returns the exit code: 1 if an error was found, 0 otherwise.
+fn print_diagnostics(diagnostics: &[Diagnostic]) -> i32 {
+    for diagnostic in diagnostics {
+        println!("{diagnostic}");
+    }
+
+    let errors = diagnostics
+        .iter()
+        .filter(|diagnostic| diagnostic.level == Level::Error)
+        .count();
+    let warnings = diagnostics.len() - errors;
+    println!("{errors} error(s), {warnings} warning(s)");
+
+    match errors {
+        0 => 0,
+        _ => 1,
+    }
+}
+
 fn main() {
-    let mut config = process_cli_config();
+    let mut config = match process_cli_config() {
+        Ok(CliAction::Run(config)) => *config,
+        Ok(CliAction::PrintSchema(schema)) => {
+            print!("{schema}");
+            std::process::exit(0);
+        }
+        Ok(CliAction::Check(diagnostics)) => std::process::exit(print_diagnostics(&diagnostics)),
+        Err(err) => {
+            eprintln!("Invalid config: {err}");
+            std::process::exit(1);
+        }
+    };
     let daemon = config.daemon.clone();
 
+    // In dmenu mode, the entries are read from stdin
+    if config.dmenu {
+        config.entries = from_lines(io::stdin().lock());
+    }
+
+    // Load additional entries from a CSV / TSV file
+    if let Some(path) = &config.entries_file {
+        match from_csv(path) {
+            Ok(entries) => config.entries.extend(entries),
+            Err(err) => {
+                eprintln!("Failed to load entries from {}: {}", path.display(), err);
+                std::process::exit(1);
+            }
+        }
+    }
+
     // Running as client
     if !config.daemon {
         if let Ok(mut stream) = UnixStream::connect(SOCKET) {
-            send_message(&config, &mut stream).unwrap();
-            return;
+            let dmenu = config.dmenu;
+            send_message(&Request::Show { config }, &mut stream).unwrap();
+
+            // Block until the daemon window is resolved
+            let response = read_message(&mut stream).unwrap_or_else(|err| Response::Error {
+                message: err.to_string(),
+            });
+            exit_with_response(response, dmenu);
         }
-        // Else, run the app in "once" mode, and load the desktop entries
+        // Else, run the app in "once" mode, and load the entries
     }
 
-    // If the app is launched as a daemon, or once with no entries,
-    // it means that the desktop entries should be used (costly to load)
-    if config.daemon || config.entries.is_empty() {
-        config.entries = from_freedesktop(&config.icon_theme);
+    // If the app is launched once with no entries (or in combi mode), they are loaded from the source
+    if !config.daemon && config.loads_source() {
+        config.entries = from_source(&config, config.source);
     }
 
     let state = match config.daemon {
@@ -45,9 +106,14 @@ fn main() {
                     .expect("Socket already present, cannot start a second daemon")
             });
 
-            AppState::Daemon(listener, config.entries)
+            // The daemon caches the entries of every source (costly to load), and keeps them up to date
+            let cache = Arc::new(RwLock::new(SourceEntries::new(config)));
+            let watched = cache.clone();
+            thread::spawn(move || watch_entries(watched));
+
+            AppState::Daemon(listener, cache)
         }
-        false => AppState::Once(config),
+        false => AppState::Once(Box::new(config)),
     };
 
     if daemon {
@@ -2,8 +2,23 @@ import { invoke } from "@tauri-apps/api/core";
 import type { Entry } from "./config";
 
 export const runCommand = (entry: Entry) => {
-  invoke("run_command", {
-    command: entry.command,
-    terminal: entry.terminal,
-  });
+  invoke("run_command", { entry });
+};
+
+/** Resolve the launcher request with the selected entries and the typed text */
+export const selectEntries = async (entries: Entry[], input: string) => {
+  await invoke("select_entries", { entries, input });
+};
+
+/**
+ * Run the script again with the selected entry (script source), which displays its next entries.
+ * Resolves to false when the script printed nothing: the selection is then final.
+ */
+export const selectScriptEntry = async (entry: Entry): Promise<boolean> => {
+  return await invoke<boolean>("select_script_entry", { entry });
+};
+
+/** Resolve the launcher request as cancelled */
+export const cancel = async () => {
+  await invoke("cancel");
 };
@@ -2,17 +2,43 @@
 
 /** A launcher entry */
 export interface Entry {
+  id: string | null;
   name: string;
   command: string;
+  url: string | null;
   icon: string | null;
   description: string | null;
   keywords: string[] | null;
+  generic_name: string | null;
+  categories: string[] | null;
+  startup_wm_class: string | null;
   terminal: boolean;
+  actions: Entry[];
+  desktop_file: string | null;
+  window: string | null;
+  info: string | null;
+  source: string | null;
+  prefix: string | null;
 
   // Utility (computed when fetched)
   allKeywords: string[];
 }
 
+/** Where the entries are loaded from, when none are given */
+export type EntrySource =
+  | "desktop"
+  | "run"
+  | "window"
+  | "ssh"
+  | "script"
+  | "combi";
+
+/** A source merged in combi mode (the config entries if no source is given) */
+export interface CombiSource {
+  source: EntrySource | null;
+  prefix: string | null;
+}
+
 /** The launcher color scheme */
 export interface Colors {
   background: string;
@@ -26,8 +52,11 @@ export interface Colors {
 /** The launcher configuration for a given screen */
 export interface Config {
   daemon: boolean;
+  dmenu: boolean;
   placeholder: string | null;
   mode: "full" | "icon" | "lines" | "compact";
+  source: EntrySource;
+  combi: CombiSource[];
   entries: Entry[];
   colors: Colors;
 }
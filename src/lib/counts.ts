@@ -12,21 +12,34 @@ export const getCounts = async () =>
 export const updateCounts = async (counts: Record<string, number>) =>
   await invoke("update_counts", { counts });
 
+/** Key under which the count of an entry is stored */
+export const entryKey = (entry: Entry) => entry.id ?? entry.name;
+
+/**
+ * Selection count of an entry.
+ * Counts used to be stored by name: they are used until the entry is selected again.
+ */
+const entryCount = (counts: Record<string, number>, entry: Entry) =>
+  counts[entryKey(entry)] ?? counts[entry.name] ?? 0;
+
 /** Sort the entries by selection count */
 export const sortEntries = (entries: Entry[]) => {
   const counts = appState.counts;
 
   return entries.sort((a, b) => {
-    const countA = counts[a.name] || 0;
-    const countB = counts[b.name] || 0;
+    const countA = entryCount(counts, a);
+    const countB = entryCount(counts, b);
     return countB - countA; // Sort in descending order
   });
 };
 
-/** Increment the count for an entry */
+/**
+ * Increment the count for an entry.
+ * Open windows are not counted: they do not outlive the session.
+ */
 export const incrementCount = async (entry: Entry) => {
-  const name = entry.name;
+  if (entry.window) return;
   const counts = appState.counts;
-  counts[name] = (counts[name] || 0) + 1;
+  counts[entryKey(entry)] = entryCount(counts, entry) + 1;
   await updateCounts(counts);
 };
@@ -1,12 +1,31 @@
 /** Load the launcher configuration from a channel */
 
 import { Channel, invoke } from "@tauri-apps/api/core";
-import { updateColors, type Config } from "./config";
+import { updateColors, type Config, type Entry } from "./config";
 import { getCurrentWindow, LogicalSize } from "@tauri-apps/api/window";
 import { appState } from "./config.svelte";
 import removeAccents from "remove-accents";
 import { getCounts } from "./counts";
 
+/** Compute the keywords used to filter an entry */
+const computeKeywords = (entry: Entry) => {
+  entry.allKeywords = removeAccents(entry.name.toLowerCase()).split(" ");
+  if (entry.keywords) {
+    entry.allKeywords.push(...entry.keywords.map((k) => k.toLowerCase()));
+  }
+  if (entry.generic_name) {
+    entry.allKeywords.push(
+      ...removeAccents(entry.generic_name.toLowerCase()).split(" "),
+    );
+  }
+  if (entry.categories) {
+    entry.allKeywords.push(...entry.categories.map((c) => c.toLowerCase()));
+  }
+  if (entry.startup_wm_class) {
+    entry.allKeywords.push(entry.startup_wm_class.toLowerCase());
+  }
+};
+
 /** Subscribe to the configuration channel */
 export const subscribeConfig = async () => {
   const channel = new Channel<Config>();
@@ -19,10 +38,8 @@ export const subscribeConfig = async () => {
 
     // Compute the keywords
     for (const entry of config.entries) {
-      entry.allKeywords = removeAccents(entry.name.toLowerCase()).split(" ");
-      if (entry.keywords) {
-        entry.allKeywords.push(...entry.keywords.map((k) => k.toLowerCase()));
-      }
+      computeKeywords(entry);
+      entry.actions.forEach(computeKeywords);
     }
 
     if (config.mode === "icon") {
@@ -14,3 +14,21 @@ export const filterEntry = (entry: Entry, substrings: string[]): boolean => {
     entry.allKeywords.some((keyword) => keyword.includes(substring)),
   );
 };
+
+/** Split a typed combi prefix (e.g. "ssh " or "!") from the search text.
+ * Prefixes ending with a letter or digit must be followed by a space.
+ */
+export const splitPrefix = (
+  prefixes: string[],
+  filter: string,
+): [string | null, string] => {
+  // The longest prefix wins (e.g. "!!" over "!")
+  const sorted = [...prefixes].sort((a, b) => b.length - a.length);
+  for (const prefix of sorted) {
+    const typed = /\w$/.test(prefix) ? `${prefix} ` : prefix;
+    if (filter.startsWith(typed)) {
+      return [prefix, filter.slice(typed.length)];
+    }
+  }
+  return [null, filter];
+};